@@ -9,6 +9,7 @@ fn main() -> std::io::Result<()> {
         allow_diagonal: true,        // Allow diagonal movement
         shelter_capacity: 100,       // Limit shelter capacity
         path_algorithm: "dijkstra".to_string(),
+        ..GridConfig::default()
     };
     
     let agent_config = AgentConfig {
@@ -18,13 +19,15 @@ fn main() -> std::io::Result<()> {
     };
     
     let simulation_config = SimulationConfig {
-        tsunami_delay: 20 * 60,      // Shorter delay before tsunami
-        tsunami_speed_time: 20,      // Faster tsunami updates
+        tsunami_delay: 20.0 * 60.0,  // Shorter delay before tsunami (seconds)
+        tsunami_speed_time: 20.0,    // Faster tsunami updates (seconds)
         distribution_weights: [10, 20, 30, 20, 20],
         base_speed: agent_config.base_speed,
         agent_speed_multipliers: agent_config.speed_multipliers,
         agent_type_weights: agent_config.type_weights,
-        data_collection_interval: 20,  // More frequent data collection
+        data_collection_interval: 20.0,  // More frequent data collection (seconds)
+        grid: grid_config.clone(),
+        ..SimulationConfig::default()
     };
     
     // Create grid with custom configuration
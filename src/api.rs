@@ -1,6 +1,12 @@
+use crate::check_frame_interval;
 use crate::game::agent::AgentType;
+use crate::game::clock::SimClock;
+use crate::game::vehicle::VehicleCounts;
 use crate::game::game::Model;
 use crate::game::grid::load_grid_from_ascii;
+use crate::game::shelter::load_shelter_catalogue;
+use crate::synthetic::SyntheticTsunami;
+use crate::tsunami::{self, TsunamiSeriesConfig};
 
 use actix_cors::Cors;
 use actix_web::{
@@ -20,6 +26,25 @@ pub struct SimulationConfig {
     pub tsunami_data_path: String,
     pub output_path: String,
     pub max_steps: Option<u32>,
+    /// File naming and subsampling of the tsunami frames
+    #[serde(default)]
+    pub tsunami_series: TsunamiSeriesConfig,
+    /// Seed for reproducible runs (default: random)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Seconds of simulated time per step (default: 1)
+    #[serde(default = "default_dt")]
+    pub dt: f64,
+    /// JSON or CSV catalogue of shelter names and attributes (default: none)
+    #[serde(default)]
+    pub shelter_catalogue_path: Option<String>,
+    /// Generated tsunami frames used instead of `tsunami_data_path` (default: none)
+    #[serde(default)]
+    pub synthetic_tsunami: Option<SyntheticTsunami>,
+}
+
+fn default_dt() -> f64 {
+    1.0
 }
 
 impl Default for SimulationConfig {
@@ -31,6 +56,11 @@ impl Default for SimulationConfig {
             tsunami_data_path: "./data_sample/tsunami_ascii_sample".to_string(),
             output_path: "./output".to_string(),
             max_steps: None,
+            tsunami_series: TsunamiSeriesConfig::default(),
+            seed: None,
+            dt: default_dt(),
+            shelter_catalogue_path: None,
+            synthetic_tsunami: None,
         }
     }
 }
@@ -63,8 +93,9 @@ impl SimulationConfig {
 }
 
 // Define some constants used in the simulation
-const TSUNAMI_DELAY: u32 = 50; // Start tsunami earlier for testing (was 30 * 60)
-const TSUNAMI_SPEED_TIME: u32 = 28;
+// Durations in seconds, converted to steps with the model clock
+const TSUNAMI_DELAY: f64 = 50.0; // Start tsunami earlier for testing (was 30 * 60)
+const TSUNAMI_SPEED_TIME: f64 = 28.0;
 
 // Current state of simulation
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -90,9 +121,17 @@ pub struct ShelterAgentTypeData {
 #[derive(Serialize, Deserialize)]
 pub struct StepResult {
     pub step: u32,
+    /// Seconds elapsed at the start of the step
+    pub time: f64,
     pub dead_agents: usize,
     pub dead_agent_types: HashMap<String, u32>,
     pub shelter_data: HashMap<String, ShelterAgentTypeData>,
+    /// Total agents turned away by full shelters so far
+    pub shelter_overflows: usize,
+    /// Vehicles parked per shelter, reported apart from the people inside
+    pub shelter_vehicles: HashMap<String, VehicleCounts>,
+    /// Vehicles caught by the tsunami and the people they carried
+    pub dead_vehicles: VehicleCounts,
 }
 
 // Application state
@@ -135,31 +174,6 @@ async fn update_config(
     }))
 }
 
-// Helper function to read tsunami data from a directory
-fn read_tsunami_data(_dir_path: &str, ncols: u32, nrows: u32) -> std::io::Result<Vec<Vec<Vec<u32>>>> {
-    // In a real implementation, this would read tsunami data from files
-    // For now, we'll create a simple mock implementation
-    let mut tsunami_data = Vec::new();
-    
-    // Create a simple tsunami wave that progresses across the grid
-    for i in 0..10 {
-        let mut grid = vec![vec![0; ncols as usize]; nrows as usize];
-        
-        // Set tsunami height in an area that moves across the grid
-        for y in 0..nrows as usize {
-            for x in 0..ncols as usize {
-                if x > i * (ncols as usize / 10) && x < (i + 2) * (ncols as usize / 10) {
-                    grid[y][x] = 10; // Tsunami height
-                }
-            }
-        }
-        
-        tsunami_data.push(grid);
-    }
-    
-    Ok(tsunami_data)
-}
-
 // Initialize simulation
 #[post("/init")]
 async fn init_simulation(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
@@ -198,8 +212,13 @@ async fn init_simulation(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responde
     // Unpack the result - load_grid_from_ascii returns (Grid, Vec<Agent>)
     let (mut grid, agents) = grid_result.unwrap();
     
-    // Load tsunami data
-    let tsunami_data_result = read_tsunami_data(&tsunami_data_path, grid.ncol, grid.nrow);
+    // Load tsunami data, or generate it for synthetic scenarios
+    let tsunami_data_result = match &app_state.config.synthetic_tsunami {
+        Some(synthetic) => check_frame_interval(synthetic.frame_interval, TSUNAMI_SPEED_TIME)
+            .map(|_| synthetic.load_into(&mut grid)),
+        None => check_frame_interval(app_state.config.tsunami_series.frame_interval(), TSUNAMI_SPEED_TIME)
+            .and_then(|_| tsunami::load_into_grid(&tsunami_data_path, &mut grid, &app_state.config.tsunami_series)),
+    };
     if tsunami_data_result.is_err() {
         return HttpResponse::InternalServerError().json(json!({
             "status": "error",
@@ -207,16 +226,24 @@ async fn init_simulation(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responde
         }));
     }
     
-    // Set tsunami data in grid
-    grid.tsunami_data = tsunami_data_result.unwrap();
-    
+    // Attach shelter names and attributes
+    if let Some(catalogue_path) = &app_state.config.shelter_catalogue_path {
+        let attached = load_shelter_catalogue(catalogue_path)
+            .and_then(|catalogue| grid.attach_shelter_catalogue(catalogue));
+        if let Err(e) = attached {
+            return HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": format!("Failed to load shelter catalogue: {}", e)
+            }));
+        }
+    }
+
     // Create model
-    let model = Model {
-        grid,
-        agents,
-        dead_agents: 0,
-        dead_agent_types: Vec::new(),
-    };
+    let mut model = Model::new(grid, agents);
+    model.clock = SimClock::new(app_state.config.dt);
+    if let Some(seed) = app_state.config.seed {
+        model.set_seed(seed);
+    }
     
     app_state.model = Some(model);
     app_state.state.is_running = true;
@@ -254,26 +281,33 @@ async fn run_step(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
         // Extract needed values before releasing the lock
         (
             app_state.state.current_step,
-            app_state.config.max_steps
+            app_state.config.max_steps,
+            SimClock::new(app_state.config.dt)
         )
     };
     
-    let (current_step, max_steps) = initialization_check;
+    let (current_step, max_steps, clock) = initialization_check;
     
     // Calculate tsunami state without holding the lock
-    let is_tsunami = current_step >= TSUNAMI_DELAY;
+    let delay_steps = clock.steps_for(TSUNAMI_DELAY);
+    let is_tsunami = current_step >= delay_steps;
     let tsunami_index = if is_tsunami {
-        ((current_step - TSUNAMI_DELAY) / TSUNAMI_SPEED_TIME) as usize
+        ((current_step - delay_steps) / clock.steps_for(TSUNAMI_SPEED_TIME).max(1)) as usize
     } else {
         0
     };
+    let time = clock.time_at(current_step);
     
     // Create result struct to store intermediate results
     let mut step_result = StepResult {
         step: current_step,
+        time,
         dead_agents: 0,
         dead_agent_types: HashMap::new(),
         shelter_data: HashMap::new(),
+        shelter_overflows: 0,
+        shelter_vehicles: HashMap::new(),
+        dead_vehicles: VehicleCounts::default(),
     };
     
     // STEP 1: Get a clone of the model for processing
@@ -292,6 +326,13 @@ async fn run_step(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
             
             // Store data for later processing
             step_result.dead_agents = model.dead_agents;
+            step_result.shelter_overflows = model.shelter_overflows.len();
+            step_result.dead_vehicles = model.dead_vehicles.clone();
+            step_result.shelter_vehicles = model
+                .parked_vehicles
+                .iter()
+                .map(|(&shelter_id, counts)| (model.grid.shelter_label(shelter_id), counts.clone()))
+                .collect();
             
             // Collect dead agent types
             for agent_type in &model.dead_agent_types {
@@ -311,7 +352,7 @@ async fn run_step(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
                         _ => {} // Handle any other agent types
                     }
                 }
-                step_result.shelter_data.insert(format!("Shelter {}", shelter_id), shelter_counts);
+                step_result.shelter_data.insert(model.grid.shelter_label(shelter_id), shelter_counts);
             }
         }
     }
@@ -327,13 +368,17 @@ async fn run_step(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
         // Create JSON data
         let death_json = json!({
             "step": current_step,
+            "time": time,
             "dead_agents": step_result.dead_agents,
-            "dead_agent_types": step_result.dead_agent_types.clone()
+            "dead_agent_types": step_result.dead_agent_types.clone(),
+            "dead_vehicles": step_result.dead_vehicles.clone()
         });
         
         let shelter_json = json!({
             "step": current_step,
-            "shelters": step_result.shelter_data.clone()
+            "time": time,
+            "shelters": step_result.shelter_data.clone(),
+            "shelter_vehicles": step_result.shelter_vehicles.clone()
         });
         
         // Add to counters
@@ -416,26 +461,33 @@ async fn run_single_step(data: web::Data<Arc<Mutex<AppState>>>) -> Result<StepRe
         // Extract needed values before releasing the lock
         (
             app_state.state.current_step,
-            app_state.config.max_steps
+            app_state.config.max_steps,
+            SimClock::new(app_state.config.dt)
         )
     };
     
-    let (current_step, max_steps) = initialization_check;
+    let (current_step, max_steps, clock) = initialization_check;
     
     // Calculate tsunami state without holding the lock
-    let is_tsunami = current_step >= TSUNAMI_DELAY;
+    let delay_steps = clock.steps_for(TSUNAMI_DELAY);
+    let is_tsunami = current_step >= delay_steps;
     let tsunami_index = if is_tsunami {
-        ((current_step - TSUNAMI_DELAY) / TSUNAMI_SPEED_TIME) as usize
+        ((current_step - delay_steps) / clock.steps_for(TSUNAMI_SPEED_TIME).max(1)) as usize
     } else {
         0
     };
+    let time = clock.time_at(current_step);
     
     // Create result struct to store intermediate results
     let mut step_result = StepResult {
         step: current_step,
+        time,
         dead_agents: 0,
         dead_agent_types: HashMap::new(),
         shelter_data: HashMap::new(),
+        shelter_overflows: 0,
+        shelter_vehicles: HashMap::new(),
+        dead_vehicles: VehicleCounts::default(),
     };
     
     // STEP 1: Get a clone of the model for processing
@@ -454,6 +506,13 @@ async fn run_single_step(data: web::Data<Arc<Mutex<AppState>>>) -> Result<StepRe
             
             // Store data for later processing
             step_result.dead_agents = model.dead_agents;
+            step_result.shelter_overflows = model.shelter_overflows.len();
+            step_result.dead_vehicles = model.dead_vehicles.clone();
+            step_result.shelter_vehicles = model
+                .parked_vehicles
+                .iter()
+                .map(|(&shelter_id, counts)| (model.grid.shelter_label(shelter_id), counts.clone()))
+                .collect();
             
             // Collect dead agent types
             for agent_type in &model.dead_agent_types {
@@ -473,7 +532,7 @@ async fn run_single_step(data: web::Data<Arc<Mutex<AppState>>>) -> Result<StepRe
                         _ => {} // Handle any other agent types
                     }
                 }
-                step_result.shelter_data.insert(format!("Shelter {}", shelter_id), shelter_counts);
+                step_result.shelter_data.insert(model.grid.shelter_label(shelter_id), shelter_counts);
             }
         }
     }
@@ -489,13 +548,17 @@ async fn run_single_step(data: web::Data<Arc<Mutex<AppState>>>) -> Result<StepRe
         // Create JSON data
         let death_json = json!({
             "step": current_step,
+            "time": time,
             "dead_agents": step_result.dead_agents,
-            "dead_agent_types": step_result.dead_agent_types.clone()
+            "dead_agent_types": step_result.dead_agent_types.clone(),
+            "dead_vehicles": step_result.dead_vehicles.clone()
         });
         
         let shelter_json = json!({
             "step": current_step,
-            "shelters": step_result.shelter_data.clone()
+            "time": time,
+            "shelters": step_result.shelter_data.clone(),
+            "shelter_vehicles": step_result.shelter_vehicles.clone()
         });
         
         // Add to counters
@@ -570,10 +633,8 @@ async fn export_results(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder
         .collect();
     
     // Include tsunami data if available
-    let tsunami_data = if app_state.state.is_tsunami && 
-                        !model.grid.tsunami_data.is_empty() && 
-                        app_state.state.tsunami_index < model.grid.tsunami_data.len() {
-        Some(&model.grid.tsunami_data[app_state.state.tsunami_index])
+    let tsunami_data = if app_state.state.is_tsunami {
+        model.grid.tsunami_frame(app_state.state.tsunami_index)
     } else {
         None
     };
@@ -587,7 +648,7 @@ async fn export_results(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder
             for y in 0..data.len() {
                 for x in 0..data[y].len() {
                     let height = data[y][x];
-                    if height > 0 {
+                    if height > 0.0 {
                         tsunami_cells.push(json!({
                             "x": x,
                             "y": y,
@@ -604,6 +665,7 @@ async fn export_results(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder
     HttpResponse::Ok().json(json!({
         "status": "ok",
         "step": app_state.state.current_step,
+        "time": model.clock.time_at(app_state.state.current_step),
         "is_tsunami": app_state.state.is_tsunami,
         "tsunami_index": app_state.state.tsunami_index,
         "agents": agents_data,
@@ -670,7 +732,7 @@ pub async fn get_grid_data(data: web::Data<Arc<Mutex<AppState>>>) -> impl Respon
             grid_data.push(row);
         }
         
-        return HttpResponse::Ok().json(json!({
+        HttpResponse::Ok().json(json!({
             "header": {
                 "ncols": ncol,
                 "nrows": nrow,
@@ -679,13 +741,25 @@ pub async fn get_grid_data(data: web::Data<Arc<Mutex<AppState>>>) -> impl Respon
                 "cellsize": cellsize,
                 "NODATA_value": nodata_value
             },
-            "grid": grid_data
-        }));
+            "grid": grid_data,
+            "shelters": grid
+                .shelters
+                .iter()
+                .map(|&(x, y, id)| json!({
+                    "id": id,
+                    "x": x,
+                    "y": y,
+                    "entrance": grid.is_shelter_entrance(x, y),
+                    "name": model.grid.shelter_label(id),
+                    "info": grid.shelter_info(id)
+                }))
+                .collect::<Vec<_>>()
+        }))
     } else {
-        return HttpResponse::BadRequest().json(json!({
+        HttpResponse::BadRequest().json(json!({
             "status": "error",
             "message": "Simulation not initialized"
-        }));
+        }))
     }
 }
 
@@ -5,16 +5,30 @@ fn main() -> std::io::Result<()> {
     let data_dir = Path::new("data");
     let grid_path = data_dir.join("grids/default_grid.asc");
     let population_path = data_dir.join("population/default_population.asc");
+    let tsunami_path = data_dir.join("tsunami");
 
-    let mut simulation = Simulation::new(
+    let config = SimulationConfig {
+        tsunami_data_path: tsunami_path
+            .is_dir()
+            .then(|| tsunami_path.to_str().unwrap().to_string()),
+        ..SimulationConfig::default()
+    };
+
+    let mut simulation = Simulation::with_config(
         grid_path.to_str().unwrap(),
-        population_path.to_str().unwrap()
+        population_path.to_str().unwrap(),
+        config
     )?;
 
+    if simulation.model.grid.tsunami_frame_count() > 0 {
+        simulation.compute_hazard_maps()?;
+    }
+
     while simulation.step() {
         println!(
-            "Step: {} Tsunami Index: {}",
-            simulation.current_step,
+            "Step: {} Time: {:.0} s Tsunami Index: {}",
+            simulation.current_step(),
+            simulation.elapsed_seconds(),
             simulation.tsunami_index
         );
     }
@@ -24,6 +38,10 @@ fn main() -> std::io::Result<()> {
         &simulation.agent_data_collector,
         "output/step.geojson"
     )?;
+    export_grid_to_geojson(&simulation.model.grid, "output/grid.geojson")?;
+    if let Some(hazard_maps) = &simulation.hazard_maps {
+        hazard_maps.write_ascii(Path::new("output/hazard"), &simulation.model.grid)?;
+    }
 
     Ok(())
 } 
\ No newline at end of file
@@ -0,0 +1,431 @@
+//! Monte Carlo ensembles: many seeded replicates of one scenario, summarised
+//! as means and confidence intervals
+
+use crate::agent::AgentType;
+use crate::clock::SimClock;
+use crate::household::HouseholdOutcome;
+use crate::{rng, Simulation, SimulationConfig};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+
+/// Percentiles of the evacuation time reported per replicate
+pub const EVACUATION_PERCENTILES: [u32; 4] = [10, 50, 90, 95];
+
+/// Runs replicates of a scenario that differ only in their seed
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    pub grid_path: String,
+    pub population_path: String,
+    /// Scenario configuration; its seed is replaced per replicate
+    pub config: SimulationConfig,
+    /// Number of replicates to run (default: 30)
+    pub replicates: usize,
+    /// Replicate `i` uses seed `base_seed + i` (default: the config seed, or random)
+    pub base_seed: u64,
+    /// Step limit per replicate (default: run until the tsunami frames end)
+    pub max_steps: Option<u32>,
+}
+
+/// Outcome of one replicate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicateResult {
+    pub seed: u64,
+    pub steps: u32,
+    /// People placed, everyone in a vehicle included
+    pub total_agents: usize,
+    /// Deaths per agent type (Child, Teen, Adult, Elder, Custom)
+    pub deaths_by_type: BTreeMap<String, usize>,
+    pub total_deaths: usize,
+    /// Cumulative shelter arrivals at every data collection interval, starting at step 0
+    pub cumulative_arrivals: Vec<usize>,
+    /// Step at which each sheltered person arrived
+    pub evacuation_steps: Vec<u32>,
+    /// Seconds elapsed when each sheltered person arrived
+    pub evacuation_times: Vec<f64>,
+    /// Sheltered, dead and still evacuating members of every household
+    #[serde(default)]
+    pub household_outcomes: Vec<HouseholdOutcome>,
+}
+
+/// Summary of one quantity across replicates; every value is None when it
+/// was observed in none of them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statistic {
+    /// Number of replicates the quantity was observed in
+    pub samples: usize,
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+    /// Bounds of the 95% confidence interval of the mean (Student's t)
+    pub ci_lower: Option<f64>,
+    pub ci_upper: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Aggregated results of an ensemble
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleSummary {
+    pub replicates: usize,
+    pub base_seed: u64,
+    pub deaths_by_type: BTreeMap<String, Statistic>,
+    pub total_deaths: Statistic,
+    /// Households that lost at least one member
+    pub households_with_deaths: Statistic,
+    /// Households whose members all reached a shelter
+    pub households_sheltered: Statistic,
+    /// Steps at which cumulative arrivals are sampled
+    pub arrival_steps: Vec<u32>,
+    /// Seconds elapsed at each of `arrival_steps`
+    pub arrival_times: Vec<f64>,
+    /// Cumulative shelter arrivals at each of `arrival_steps`
+    pub cumulative_arrivals: Vec<Statistic>,
+    /// Evacuation time percentiles in seconds, keyed "p10", "p50", ...
+    pub evacuation_time_percentiles: BTreeMap<String, Statistic>,
+}
+
+/// Per-replicate results together with their summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleResult {
+    pub summary: EnsembleSummary,
+    pub replicates: Vec<ReplicateResult>,
+}
+
+impl Ensemble {
+    pub fn new(grid_path: &str, population_path: &str, config: SimulationConfig) -> Self {
+        let base_seed = config.seed.unwrap_or_else(rng::random_seed);
+        Ensemble {
+            grid_path: grid_path.to_string(),
+            population_path: population_path.to_string(),
+            config,
+            replicates: 30,
+            base_seed,
+            max_steps: None,
+        }
+    }
+
+    /// Builder-style method to set the number of replicates
+    pub fn with_replicates(mut self, replicates: usize) -> Self {
+        self.replicates = replicates;
+        self
+    }
+
+    /// Builder-style method to set the seed of the first replicate
+    pub fn with_base_seed(mut self, seed: u64) -> Self {
+        self.base_seed = seed;
+        self
+    }
+
+    /// Builder-style method to set the step limit per replicate
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Run every replicate in parallel and aggregate the results
+    pub fn run(&self) -> io::Result<EnsembleResult> {
+        let replicates: Vec<ReplicateResult> = (0..self.replicates)
+            .into_par_iter()
+            .map(|i| self.run_replicate(self.base_seed.wrapping_add(i as u64)))
+            .collect::<io::Result<_>>()?;
+
+        let clock = SimClock::new(self.config.dt);
+        Ok(EnsembleResult {
+            summary: summarize(
+                &replicates,
+                self.base_seed,
+                clock.steps_for(self.config.data_collection_interval),
+                clock.dt,
+            ),
+            replicates,
+        })
+    }
+
+    /// Run a single replicate with the given seed
+    pub fn run_replicate(&self, seed: u64) -> io::Result<ReplicateResult> {
+        let config = SimulationConfig {
+            seed: Some(seed),
+            ..self.config.clone()
+        };
+        let mut simulation = Simulation::with_config(&self.grid_path, &self.population_path, config)?;
+
+        if simulation.model.grid.tsunami_frame_count() == 0 && self.max_steps.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Ensemble needs tsunami data or a step limit to end each replicate",
+            ));
+        }
+
+        let total_agents: usize = simulation.model.agents.iter().map(|agent| agent.people() as usize).sum();
+        simulation.run(self.max_steps)?;
+
+        let mut deaths_by_type: BTreeMap<String, usize> = ["Child", "Teen", "Adult", "Elder"]
+            .iter()
+            .map(|&name| (name.to_string(), 0))
+            .collect();
+        for agent_type in &simulation.model.dead_agent_types {
+            *deaths_by_type.entry(agent_type_label(*agent_type)).or_insert(0) += 1;
+        }
+
+        // Everyone in a vehicle arrives with its driver
+        let evacuation_steps: Vec<u32> = simulation
+            .model
+            .shelter_arrivals
+            .iter()
+            .flat_map(|arrival| std::iter::repeat_n(arrival.step, arrival.people()))
+            .collect();
+        let evacuation_times: Vec<f64> = simulation
+            .model
+            .shelter_arrivals
+            .iter()
+            .flat_map(|arrival| std::iter::repeat_n(arrival.time, arrival.people()))
+            .collect();
+
+        // Sample points run past the last executed step so the series ends at the total
+        let interval = simulation
+            .model
+            .clock
+            .steps_for(simulation.config.data_collection_interval)
+            .max(1);
+        let last_step = simulation.current_step().saturating_sub(1);
+        let cumulative_arrivals = (0..=last_step.div_ceil(interval))
+            .map(|k| evacuation_steps.iter().filter(|&&step| step <= k * interval).count())
+            .collect();
+
+        Ok(ReplicateResult {
+            seed,
+            steps: simulation.current_step(),
+            total_agents,
+            total_deaths: simulation.model.dead_agents,
+            deaths_by_type,
+            cumulative_arrivals,
+            evacuation_steps,
+            evacuation_times,
+            household_outcomes: simulation.model.household_outcomes(),
+        })
+    }
+}
+
+fn agent_type_label(agent_type: AgentType) -> String {
+    match agent_type {
+        AgentType::Child => "Child",
+        AgentType::Teen => "Teen",
+        AgentType::Adult => "Adult",
+        AgentType::Elder => "Elder",
+        AgentType::Custom(_) => "Custom",
+    }
+    .to_string()
+}
+
+/// Aggregate replicate results; series of different lengths are padded with
+/// their final value. `interval` is the number of steps between arrival
+/// samples and `dt` the seconds per step.
+pub fn summarize(replicates: &[ReplicateResult], base_seed: u64, interval: u32, dt: f64) -> EnsembleSummary {
+    let mut death_types: Vec<String> = replicates
+        .iter()
+        .flat_map(|r| r.deaths_by_type.keys().cloned())
+        .collect();
+    death_types.sort();
+    death_types.dedup();
+
+    let deaths_by_type = death_types
+        .into_iter()
+        .map(|name| {
+            let values: Vec<f64> = replicates
+                .iter()
+                .map(|r| r.deaths_by_type.get(&name).copied().unwrap_or(0) as f64)
+                .collect();
+            (name, Statistic::from_samples(&values))
+        })
+        .collect();
+
+    let total_deaths = Statistic::from_samples(
+        &replicates.iter().map(|r| r.total_deaths as f64).collect::<Vec<_>>(),
+    );
+
+    let households_with_deaths = Statistic::from_samples(
+        &replicates
+            .iter()
+            .map(|r| r.household_outcomes.iter().filter(|h| h.dead > 0).count() as f64)
+            .collect::<Vec<_>>(),
+    );
+    let households_sheltered = Statistic::from_samples(
+        &replicates
+            .iter()
+            .map(|r| r.household_outcomes.iter().filter(|h| h.sheltered == h.size).count() as f64)
+            .collect::<Vec<_>>(),
+    );
+
+    let series_len = replicates
+        .iter()
+        .map(|r| r.cumulative_arrivals.len())
+        .max()
+        .unwrap_or(0);
+    let cumulative_arrivals = (0..series_len)
+        .map(|k| {
+            let values: Vec<f64> = replicates
+                .iter()
+                .filter_map(|r| r.cumulative_arrivals.get(k).or(r.cumulative_arrivals.last()))
+                .map(|&count| count as f64)
+                .collect();
+            Statistic::from_samples(&values)
+        })
+        .collect();
+    let arrival_steps: Vec<u32> = (0..series_len as u32).map(|k| k * interval.max(1)).collect();
+    let arrival_times = arrival_steps.iter().map(|&step| step as f64 * dt).collect();
+
+    let evacuation_time_percentiles = EVACUATION_PERCENTILES
+        .iter()
+        .map(|&p| {
+            let values: Vec<f64> = replicates
+                .iter()
+                .filter_map(|r| percentile(&r.evacuation_times, p))
+                .collect();
+            (format!("p{}", p), Statistic::from_samples(&values))
+        })
+        .collect();
+
+    EnsembleSummary {
+        replicates: replicates.len(),
+        base_seed,
+        deaths_by_type,
+        total_deaths,
+        households_with_deaths,
+        households_sheltered,
+        arrival_steps,
+        arrival_times,
+        cumulative_arrivals,
+        evacuation_time_percentiles,
+    }
+}
+
+/// Nearest-rank percentile of a set of times, None if empty
+fn percentile(times: &[f64], p: u32) -> Option<f64> {
+    if times.is_empty() {
+        return None;
+    }
+    let mut sorted = times.to_vec();
+    sorted.sort_unstable_by(f64::total_cmp);
+    let rank = (p as f64 / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl Statistic {
+    /// Mean, sample standard deviation and 95% confidence interval of the mean.
+    /// With fewer than two samples the interval collapses to the mean.
+    pub fn from_samples(values: &[f64]) -> Self {
+        let n = values.len();
+        if n == 0 {
+            return Statistic {
+                samples: 0,
+                mean: None,
+                std_dev: None,
+                ci_lower: None,
+                ci_upper: None,
+                min: None,
+                max: None,
+            };
+        }
+
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let std_dev = if n > 1 {
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let half_width = if n > 1 {
+            t_critical_95(n - 1) * std_dev / (n as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        Statistic {
+            samples: n,
+            mean: Some(mean),
+            std_dev: Some(std_dev),
+            ci_lower: Some(mean - half_width),
+            ci_upper: Some(mean + half_width),
+            min: Some(values.iter().copied().fold(f64::INFINITY, f64::min)),
+            max: Some(values.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        }
+    }
+}
+
+/// Two-sided 95% critical value of Student's t distribution
+fn t_critical_95(degrees_of_freedom: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179,
+        2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064,
+        2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    match degrees_of_freedom {
+        0 => f64::NAN,
+        df if df <= TABLE.len() => TABLE[df - 1],
+        df if df <= 60 => 2.000,
+        df if df <= 120 => 1.980,
+        _ => 1.960,
+    }
+}
+
+impl EnsembleResult {
+    /// Write the summary and every replicate as pretty-printed JSON
+    pub fn write_json(&self, path: &str) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Write the summary as CSV, one row per quantity
+    pub fn write_csv(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "metric,samples,mean,std_dev,ci_lower,ci_upper,min,max")?;
+
+        let summary = &self.summary;
+        let mut rows: Vec<(String, &Statistic)> = vec![
+            ("deaths.total".to_string(), &summary.total_deaths),
+            ("households.with_deaths".to_string(), &summary.households_with_deaths),
+            ("households.sheltered".to_string(), &summary.households_sheltered),
+        ];
+        rows.extend(
+            summary
+                .deaths_by_type
+                .iter()
+                .map(|(name, stat)| (format!("deaths.{}", name), stat)),
+        );
+        rows.extend(
+            summary
+                .arrival_steps
+                .iter()
+                .zip(&summary.arrival_times)
+                .zip(&summary.cumulative_arrivals)
+                .map(|((step, time), stat)| (format!("arrivals.step_{}_{}s", step, time), stat)),
+        );
+        rows.extend(
+            summary
+                .evacuation_time_percentiles
+                .iter()
+                .map(|(name, stat)| (format!("evacuation_time.{}", name), stat)),
+        );
+
+        // Values of quantities no replicate observed are left empty
+        let value = |value: Option<f64>| value.map_or(String::new(), |value| value.to_string());
+        for (metric, stat) in rows {
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{},{}",
+                metric,
+                stat.samples,
+                value(stat.mean),
+                value(stat.std_dev),
+                value(stat.ci_lower),
+                value(stat.ci_upper),
+                value(stat.min),
+                value(stat.max)
+            )?;
+        }
+
+        Ok(())
+    }
+}
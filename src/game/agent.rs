@@ -2,6 +2,10 @@ use rand::prelude::*;
 use rand::distributions::WeightedIndex;
 use serde::{Serialize, Deserialize};
 use std::fmt;
+use std::io;
+
+use super::knowledge::RouteKnowledge;
+use super::vehicle::{Vehicle, VehicleKind, VehicleSpec};
 
 /// Represents different types of agents in the simulation
 #[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
@@ -14,6 +18,20 @@ pub enum AgentType {
     Custom(f64),
 }
 
+impl AgentType {
+    /// Position of the type in per-type arrays [Child, Teen, Adult, Elder];
+    /// custom types share the adult profile
+    pub fn profile_index(&self) -> usize {
+        match self {
+            AgentType::Child => 0,
+            AgentType::Teen => 1,
+            AgentType::Adult => 2,
+            AgentType::Elder => 3,
+            AgentType::Custom(_) => 2,
+        }
+    }
+}
+
 impl fmt::Display for AgentType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -56,22 +74,55 @@ pub struct Agent {
     pub x: u32,
     /// Y-coordinate of the agent's position
     pub y: u32,
-    /// Movement speed in units per time step
-    pub speed: u32,
-    /// Remaining steps the agent can move in the current time step
-    pub remaining_steps: u32,
+    /// Walking speed in metres per second
+    pub speed: f64,
+    /// Movement budget in cells; the fraction left after moving carries over
+    /// to the next time step
+    #[serde(default)]
+    pub progress: f64,
     /// Whether the agent is on a road
     pub is_on_road: bool,
     /// Type of the agent
     pub agent_type: AgentType,
     /// Whether the agent is alive
     pub is_alive: bool,
+    /// Shelter the agent is heading for (None = follow the nearest-shelter field)
+    #[serde(default)]
+    pub target_shelter: Option<u32>,
+    /// Personal vulnerability quantile compared against fragility curves,
+    /// drawn the first time the agent is caught in water
+    #[serde(default)]
+    pub fragility_quantile: Option<f64>,
+    /// Step at which the agent starts evacuating; it stays put before then
+    #[serde(default)]
+    pub departure_step: u32,
+    /// Vehicle the agent drives, None for pedestrians. A vehicle moves on
+    /// roads only and carries its occupants with it.
+    #[serde(default)]
+    pub vehicle: Option<Vehicle>,
+    /// Household the agent evacuates with, None for agents on their own
+    #[serde(default)]
+    pub household: Option<usize>,
+    /// Whether the agent knows the route or finds its way from what it sees
+    #[serde(default)]
+    pub knowledge: RouteKnowledge,
+    /// Direction of the agent's last move, None before it has moved
+    #[serde(default)]
+    pub heading: Option<(i32, i32)>,
+    /// Cell the agent started in, None if unknown
+    #[serde(default)]
+    pub origin: Option<(u32, u32)>,
 }
 
 // Legacy constant for backward compatibility
 pub const BASE_SPEED: f64 = 2.66;
 
 impl Agent {
+    /// Cell the agent started in, or its current cell if that is unknown
+    pub fn origin_cell(&self) -> (u32, u32) {
+        self.origin.unwrap_or((self.x, self.y))
+    }
+
     /// Create a new agent with default configuration
     pub fn new(id: usize, x: u32, y: u32, agent_type: AgentType, is_on_road: bool) -> Self {
         Self::with_config(id, x, y, agent_type, is_on_road, &AgentConfig::default())
@@ -85,17 +136,57 @@ impl Agent {
             AgentType::Adult => config.speed_multipliers[2] * config.base_speed,
             AgentType::Elder => config.speed_multipliers[3] * config.base_speed,
             AgentType::Custom(multiplier) => multiplier * config.base_speed,
-        } as u32;
+        };
 
         Agent {
             id,
             x,
             y,
             speed,
-            remaining_steps: speed,
+            progress: 0.0,
             is_on_road,
             agent_type,
-            is_alive: true
+            is_alive: true,
+            target_shelter: None,
+            fragility_quantile: None,
+            departure_step: 0,
+            vehicle: None,
+            household: None,
+            knowledge: RouteKnowledge::Informed,
+            heading: None,
+            origin: Some((x, y)),
+        }
+    }
+
+    /// Turn the agent into the driver of a vehicle, moving at its speed with
+    /// `passengers` on board, as many as fit besides the driver
+    pub fn drive(&mut self, kind: VehicleKind, spec: &VehicleSpec, mut passengers: Vec<AgentType>) {
+        self.speed = spec.speed;
+        passengers.truncate(spec.occupancy.saturating_sub(1) as usize);
+        self.vehicle = Some(Vehicle { kind, passengers });
+    }
+
+    /// People the agent stands for: one pedestrian, or a vehicle's occupants
+    pub fn people(&self) -> u32 {
+        self.vehicle.as_ref().map_or(1, Vehicle::occupants)
+    }
+
+    /// Type of everyone the agent stands for, the driver first
+    pub fn people_types(&self) -> impl Iterator<Item = AgentType> + '_ {
+        std::iter::once(self.agent_type).chain(
+            self.vehicle
+                .iter()
+                .flat_map(|vehicle| vehicle.passengers.iter().copied()),
+        )
+    }
+
+    /// Cells covered in one time step of `dt` seconds on a grid with square
+    /// cells `cellsize` metres wide
+    pub fn cells_per_step(&self, cellsize: f64, dt: f64) -> f64 {
+        if cellsize > 0.0 {
+            self.speed * dt / cellsize
+        } else {
+            0.0
         }
     }
 }
@@ -103,11 +194,22 @@ impl Agent {
 impl AgentType {
     /// Generate a random agent type based on default distribution
     pub fn random() -> Self {
-        Self::random_with_weights(&[6.21, 13.41, 59.10, 19.89])
+        Self::sample(&mut thread_rng())
+    }
+
+    /// Draw an agent type based on default distribution from the given generator
+    pub fn sample<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::sample_with_weights(&[6.21, 13.41, 59.10, 19.89], rng).expect("default agent type weights are valid")
     }
     
     /// Generate a random agent type with custom distribution weights
-    pub fn random_with_weights(weights: &[f64]) -> Self {
+    pub fn random_with_weights(weights: &[f64]) -> io::Result<Self> {
+        Self::sample_with_weights(weights, &mut thread_rng())
+    }
+
+    /// Draw an agent type with custom distribution weights from the given generator.
+    /// Fails if no type can be drawn, e.g. when every weight is zero.
+    pub fn sample_with_weights<R: Rng + ?Sized>(weights: &[f64], rng: &mut R) -> io::Result<Self> {
         let variants = [
             AgentType::Child,
             AgentType::Teen,
@@ -115,9 +217,10 @@ impl AgentType {
             AgentType::Elder,
         ];
 
-        let mut rng = thread_rng();
-        let dist = WeightedIndex::new(weights).unwrap();
-        variants[dist.sample(&mut rng)]
+        let dist = WeightedIndex::new(weights).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid agent type weights {:?}: {}", weights, e))
+        })?;
+        Ok(variants[dist.sample(rng)])
     }
     
     /// Get the speed multiplier for this agent type
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// Simulated time: the index of the current step and the fixed number of
+/// seconds each step covers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimClock {
+    /// Seconds of simulated time per step
+    pub dt: f64,
+    /// Index of the current step
+    pub step: u32,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        SimClock { dt: 1.0, step: 0 }
+    }
+}
+
+impl SimClock {
+    /// Clock at step 0 with steps of `dt` seconds
+    pub fn new(dt: f64) -> Self {
+        SimClock { dt, step: 0 }
+    }
+
+    /// Seconds elapsed at the start of the current step
+    pub fn elapsed(&self) -> f64 {
+        self.time_at(self.step)
+    }
+
+    /// Seconds elapsed at the start of a step
+    pub fn time_at(&self, step: u32) -> f64 {
+        step as f64 * self.dt
+    }
+
+    /// Number of steps covering a duration in seconds, rounded to the nearest step
+    pub fn steps_for(&self, seconds: f64) -> u32 {
+        if self.dt > 0.0 {
+            (seconds / self.dt).round().max(0.0) as u32
+        } else {
+            0
+        }
+    }
+
+    /// Advance to the next step
+    pub fn tick(&mut self) {
+        self.step += 1;
+    }
+}
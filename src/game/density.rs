@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Pedestrian fundamental diagram: walking speed as a fraction of the free
+/// walking speed, given the local crowd density in persons per square metre
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum FundamentalDiagram {
+    /// Speed does not depend on density and cells hold one agent each
+    #[default]
+    None,
+    /// Weidmann (1993): `1 - exp(-gamma * (1/density - 1/jam_density))`
+    Weidmann { gamma: f64, jam_density: f64 },
+    /// Fruin levels of service A-F for walkways, with the speed of each
+    /// level as a fraction of free walking speed
+    Fruin,
+    /// User-supplied (density, speed fraction) points, interpolated linearly
+    /// and held constant beyond the first and last point
+    Table { points: Vec<(f64, f64)> },
+}
+
+/// Upper density bound of Fruin levels of service A-E for walkways and the
+/// speed fraction of each level; level F covers everything denser
+const FRUIN_LEVELS: [(f64, f64); 5] = [
+    (0.31, 1.0),
+    (0.43, 0.97),
+    (0.72, 0.9),
+    (1.08, 0.8),
+    (2.17, 0.55),
+];
+const FRUIN_LEVEL_F: f64 = 0.2;
+
+impl FundamentalDiagram {
+    /// Weidmann's diagram with his fitted parameters
+    pub fn weidmann() -> Self {
+        FundamentalDiagram::Weidmann {
+            gamma: 1.913,
+            jam_density: 5.4,
+        }
+    }
+
+    /// Table diagram from (density, speed fraction) points, which must have
+    /// finite, distinct densities
+    pub fn table(mut points: Vec<(f64, f64)>) -> io::Result<Self> {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.iter().any(|&(density, factor)| !density.is_finite() || !factor.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Fundamental diagram points must be finite",
+            ));
+        }
+        if let Some(pair) = points.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Fundamental diagram has two points at density {}", pair[0].0),
+            ));
+        }
+        Ok(FundamentalDiagram::Table { points })
+    }
+
+    /// Fraction of free walking speed at a density in persons per square metre
+    pub fn speed_factor(&self, density: f64) -> f64 {
+        let factor = match self {
+            FundamentalDiagram::None => 1.0,
+            FundamentalDiagram::Weidmann { gamma, jam_density } => {
+                if density <= 0.0 {
+                    1.0
+                } else if density >= *jam_density {
+                    0.0
+                } else {
+                    1.0 - (-gamma * (1.0 / density - 1.0 / jam_density)).exp()
+                }
+            }
+            FundamentalDiagram::Fruin => FRUIN_LEVELS
+                .iter()
+                .find(|&&(upper, _)| density < upper)
+                .map_or(FRUIN_LEVEL_F, |&(_, factor)| factor),
+            FundamentalDiagram::Table { points } => {
+                let mut points = points.clone();
+                points.sort_by(|a, b| a.0.total_cmp(&b.0));
+                match points.iter().position(|&(d, _)| d >= density) {
+                    None => points.last().map_or(1.0, |&(_, factor)| factor),
+                    Some(0) => points[0].1,
+                    Some(i) => {
+                        let (d0, f0) = points[i - 1];
+                        let (d1, f1) = points[i];
+                        if d1 > d0 && d0.is_finite() {
+                            f0 + (f1 - f0) * (density - d0) / (d1 - d0)
+                        } else {
+                            f1
+                        }
+                    }
+                }
+            }
+        };
+        factor.clamp(0.0, 1.0)
+    }
+}
+
+/// Crowding model: how local density slows agents and how many agents share a cell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DensityConfig {
+    /// Speed-density relation (default: none, one agent per cell)
+    pub diagram: FundamentalDiagram,
+    /// Cells on each side of an agent counted towards its local density (default: 1)
+    pub radius: u32,
+    /// Highest density a cell can hold, in persons per square metre, on grids
+    /// without per-terrain densities (default: 5.4)
+    pub max_density: f64,
+}
+
+impl Default for DensityConfig {
+    fn default() -> Self {
+        DensityConfig {
+            diagram: FundamentalDiagram::None,
+            radius: 1,
+            max_density: 5.4,
+        }
+    }
+}
+
+impl DensityConfig {
+    /// Crowding with the given diagram and default neighbourhood and capacity
+    pub fn with_diagram(diagram: FundamentalDiagram) -> Self {
+        DensityConfig {
+            diagram,
+            ..DensityConfig::default()
+        }
+    }
+
+    /// Whether speeds depend on density
+    pub fn is_enabled(&self) -> bool {
+        self.diagram != FundamentalDiagram::None
+    }
+
+    /// Agents that fit in one square cell `cellsize` metres wide, at least one
+    pub fn cell_capacity(&self, cellsize: f64) -> usize {
+        if !self.is_enabled() {
+            return 1;
+        }
+        ((self.max_density * cellsize * cellsize).floor() as usize).max(1)
+    }
+}
@@ -0,0 +1,93 @@
+use super::agent::AgentType;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Distribution of the delay, in seconds, between the warning and an agent
+/// starting to evacuate (milling time)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum DepartureDistribution {
+    /// Leave as soon as the warning is issued
+    #[default]
+    Immediate,
+    /// Leave a fixed number of seconds after the warning
+    Fixed(f64),
+    /// Uniform between `min` and `max` seconds
+    Uniform { min: f64, max: f64 },
+    /// Rayleigh distribution with scale `sigma` seconds (mode at `sigma`),
+    /// commonly used for evacuation preparation times
+    Rayleigh { sigma: f64 },
+    /// Empirical milling-time curve as (delay in seconds, cumulative fraction
+    /// departed) points, interpolated linearly
+    Empirical { points: Vec<(f64, f64)> },
+}
+
+impl DepartureDistribution {
+    /// Delay in seconds at cumulative probability `u` in [0, 1)
+    pub fn quantile(&self, u: f64) -> f64 {
+        let delay = match self {
+            DepartureDistribution::Immediate => 0.0,
+            DepartureDistribution::Fixed(delay) => *delay,
+            DepartureDistribution::Uniform { min, max } => min + (max - min) * u,
+            DepartureDistribution::Rayleigh { sigma } => sigma * (-2.0 * (1.0 - u).ln()).sqrt(),
+            DepartureDistribution::Empirical { points } => {
+                let mut points = points.clone();
+                points.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+                match points.iter().position(|&(_, fraction)| fraction >= u) {
+                    None => points.last().map_or(0.0, |&(delay, _)| delay),
+                    Some(0) => points[0].0,
+                    Some(i) => {
+                        let (d0, f0) = points[i - 1];
+                        let (d1, f1) = points[i];
+                        if f1 > f0 {
+                            d0 + (d1 - d0) * (u - f0) / (f1 - f0)
+                        } else {
+                            d1
+                        }
+                    }
+                }
+            }
+        };
+        delay.max(0.0)
+    }
+
+    /// Draw a delay in seconds
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            DepartureDistribution::Immediate => 0.0,
+            DepartureDistribution::Fixed(delay) => delay.max(0.0),
+            _ => self.quantile(rng.gen()),
+        }
+    }
+}
+
+/// Milling-time distributions per agent type, indexed [Child, Teen, Adult, Elder].
+/// Custom agent types use the Adult distribution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct DepartureConfig {
+    pub milling: [DepartureDistribution; 4],
+}
+
+impl DepartureConfig {
+    /// The same distribution for every agent type
+    pub fn uniform_for_all(distribution: DepartureDistribution) -> Self {
+        DepartureConfig {
+            milling: [
+                distribution.clone(),
+                distribution.clone(),
+                distribution.clone(),
+                distribution,
+            ],
+        }
+    }
+
+    /// Distribution for an agent type
+    pub fn distribution(&self, agent_type: AgentType) -> &DepartureDistribution {
+        &self.milling[agent_type.profile_index()]
+    }
+
+    /// Time in seconds at which an agent of this type starts evacuating,
+    /// given the time the official warning is issued at
+    pub fn departure_time<R: Rng + ?Sized>(&self, agent_type: AgentType, warning_time: f64, rng: &mut R) -> f64 {
+        warning_time.max(0.0) + self.distribution(agent_type).sample(rng)
+    }
+}
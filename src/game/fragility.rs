@@ -0,0 +1,145 @@
+use super::agent::AgentType;
+use serde::{Deserialize, Serialize};
+
+/// Relationship between inundation depth (metres) and the probability of an outcome
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FragilityCurve {
+    /// Certain outcome on any wet cell (depth > 0)
+    AnyDepth,
+    /// Certain outcome once depth reaches the threshold in metres, none below it
+    Threshold(f64),
+    /// Lognormal fragility curve: `median` depth in metres at which the
+    /// probability is 0.5, and `beta` the standard deviation of ln(depth)
+    Lognormal { median: f64, beta: f64 },
+    /// Outcome never occurs
+    Never,
+}
+
+impl FragilityCurve {
+    /// Probability of the outcome at the given depth in metres
+    pub fn probability(&self, depth: f64) -> f64 {
+        if depth <= 0.0 {
+            return 0.0;
+        }
+
+        match *self {
+            FragilityCurve::AnyDepth => 1.0,
+            FragilityCurve::Threshold(threshold) => {
+                if depth >= threshold {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            FragilityCurve::Lognormal { median, beta } => {
+                if median <= 0.0 {
+                    1.0
+                } else if beta <= 0.0 {
+                    // Degenerate curve collapses to a step at the median
+                    if depth >= median { 1.0 } else { 0.0 }
+                } else {
+                    standard_normal_cdf((depth / median).ln() / beta)
+                }
+            }
+            FragilityCurve::Never => 0.0,
+        }
+    }
+}
+
+/// What happens to an agent standing in water of a given depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HazardOutcome {
+    Unharmed,
+    /// Swept or slowed by the flow but alive; movement is reduced for the step
+    Slowed,
+    Dead,
+}
+
+/// Fragility curves per agent type, indexed [Child, Teen, Adult, Elder].
+/// Custom agent types use the Adult curves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FragilityConfig {
+    /// Probability of death as a function of depth
+    pub mortality: [FragilityCurve; 4],
+    /// Probability of being swept off balance or slowed as a function of depth
+    pub impairment: [FragilityCurve; 4],
+    /// Fraction of its normal movement a slowed agent keeps for the step
+    pub slowed_speed_factor: f64,
+}
+
+impl Default for FragilityConfig {
+    /// Any water is fatal, matching the original binary casualty model
+    fn default() -> Self {
+        FragilityConfig {
+            mortality: [FragilityCurve::AnyDepth; 4],
+            impairment: [FragilityCurve::Never; 4],
+            slowed_speed_factor: 0.5,
+        }
+    }
+}
+
+impl FragilityConfig {
+    /// Step thresholds: 0.5 m is fatal for children and elders, 1.0 m for
+    /// teens and adults, and anyone in 0.3 m or more is slowed
+    pub fn step_thresholds() -> Self {
+        FragilityConfig {
+            mortality: [
+                FragilityCurve::Threshold(0.5),
+                FragilityCurve::Threshold(1.0),
+                FragilityCurve::Threshold(1.0),
+                FragilityCurve::Threshold(0.5),
+            ],
+            impairment: [FragilityCurve::Threshold(0.3); 4],
+            slowed_speed_factor: 0.5,
+        }
+    }
+
+    /// The same lognormal mortality curve for every agent type, no impairment
+    pub fn lognormal(median: f64, beta: f64) -> Self {
+        FragilityConfig {
+            mortality: [FragilityCurve::Lognormal { median, beta }; 4],
+            impairment: [FragilityCurve::Never; 4],
+            slowed_speed_factor: 0.5,
+        }
+    }
+
+    /// Probability of death for an agent type at the given depth
+    pub fn mortality_probability(&self, agent_type: AgentType, depth: f64) -> f64 {
+        self.mortality[agent_type.profile_index()].probability(depth)
+    }
+
+    /// Probability of being slowed for an agent type at the given depth
+    pub fn impairment_probability(&self, agent_type: AgentType, depth: f64) -> f64 {
+        self.impairment[agent_type.profile_index()].probability(depth)
+    }
+
+    /// Outcome for an agent whose personal vulnerability quantile is `quantile`
+    /// (uniform in [0, 1)). Drawing the quantile once per agent keeps the chance
+    /// of death at a depth equal to the curve value, however many steps the
+    /// agent spends in the water.
+    pub fn outcome(&self, agent_type: AgentType, depth: f64, quantile: f64) -> HazardOutcome {
+        if quantile < self.mortality_probability(agent_type, depth) {
+            HazardOutcome::Dead
+        } else if quantile < self.impairment_probability(agent_type, depth) {
+            HazardOutcome::Slowed
+        } else {
+            HazardOutcome::Unharmed
+        }
+    }
+}
+
+/// Standard normal cumulative distribution function
+fn standard_normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+/// Error function (Abramowitz and Stegun 7.1.26, absolute error below 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    sign * (1.0 - poly * (-x * x).exp())
+}
@@ -1,6 +1,19 @@
 use super::agent::{Agent, AgentType};
-use super::grid::{Grid, Terrain};
+use super::clock::SimClock;
+use super::density::DensityConfig;
+use super::departure::DepartureConfig;
+use super::fragility::{FragilityConfig, HazardOutcome};
+use super::grid::{Grid, PathPlan, Terrain};
+use super::household::{Household, HouseholdOutcome};
+use super::knowledge::{KnowledgeConfig, RouteKnowledge};
+use super::rng::{RngStreams, SimRng};
+use super::shelter::ShelterChoicePolicy;
+use super::vehicle::{Vehicle, VehicleConfig, VehicleCounts};
+use crate::tsunami::{DepthSampler, FrameInterpolation, MaxDepthEnvelope};
 use rand::seq::SliceRandom;
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
@@ -10,81 +23,640 @@ pub struct Model {
     pub agents: Vec<Agent>,
     pub dead_agents: usize,
     pub dead_agent_types: Vec<AgentType>,
+    /// Agents turned away at the door of a full shelter
+    pub shelter_overflows: Vec<ShelterOverflowEvent>,
+    /// Agents admitted to a shelter, in order of arrival
+    pub shelter_arrivals: Vec<ShelterArrivalEvent>,
+    /// How agents pick the shelter they evacuate to
+    pub shelter_choice: ShelterChoicePolicy,
+    /// Depth-dependent mortality and impairment curves
+    pub fragility: FragilityConfig,
+    /// How depth is sampled between tsunami frames
+    pub tsunami_interpolation: FrameInterpolation,
+    /// Step being simulated and the seconds each step covers
+    pub clock: SimClock,
+    /// Density-dependent walking speed and cell capacity
+    pub density: DensityConfig,
+    /// Vehicle characteristics, mode share and shelter parking
+    pub vehicles: VehicleConfig,
+    /// Vehicles parked at each shelter, keyed by shelter ID
+    pub parked_vehicles: HashMap<u32, VehicleCounts>,
+    /// Vehicles caught by the tsunami, with the people inside them
+    pub dead_vehicles: VehicleCounts,
+    /// Households whose members gather and evacuate together
+    pub households: Vec<Household>,
+    /// Seconds a household spends gathering before it leaves
+    pub gathering_time: f64,
+    /// Who knows the route and how far the others perceive
+    pub knowledge: KnowledgeConfig,
+    /// Deepest water seen so far per cell, kept for `FrameInterpolation::MaxSoFar`
+    tsunami_envelope: MaxDepthEnvelope,
+    /// Tsunami frame the shelter routes were last updated for
+    routed_frame: Option<usize>,
+    /// Agents on each floor of the evacuation buildings, keyed by shelter ID
+    pub shelter_floors: HashMap<u32, Vec<Vec<FloorOccupant>>>,
+    /// People the stairs of each building can still take in this step
+    stair_allowance: HashMap<u32, f64>,
+    /// Floors of evacuation buildings reached by the water
+    pub shelter_failures: Vec<ShelterFailureEvent>,
+    /// A* routes agents follow when the grid's `path_algorithm` is "a_star",
+    /// keyed by agent id
+    pub agent_paths: HashMap<usize, PathPlan>,
+    /// Seeded random streams for movement, shelter choice and the hazard model
+    pub rng: RngStreams,
+}
+
+/// Record of an agent rejected by a shelter that had reached its capacity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShelterOverflowEvent {
+    pub step: u32,
+    /// Seconds elapsed at the start of the step
+    pub time: f64,
+    pub shelter_id: u32,
+    pub agent_id: usize,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Record of an agent admitted to a shelter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShelterArrivalEvent {
+    pub step: u32,
+    /// Seconds elapsed at the start of the step
+    pub time: f64,
+    pub shelter_id: u32,
+    pub agent_id: usize,
+    pub agent_type: AgentType,
+    /// Vehicle the agent arrived in, None on foot
+    #[serde(default)]
+    pub vehicle: Option<Vehicle>,
+    /// Whether the agent knew the route
+    #[serde(default)]
+    pub knowledge: RouteKnowledge,
+}
+
+impl ShelterArrivalEvent {
+    /// People admitted: the agent, or everyone in its vehicle
+    pub fn people(&self) -> usize {
+        self.vehicle.as_ref().map_or(1, |vehicle| vehicle.occupants() as usize)
+    }
+}
+
+/// Agent sheltering on a floor of an evacuation building, with everyone it stands for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FloorOccupant {
+    pub agent_id: usize,
+    pub people: usize,
+}
+
+/// Record of a floor of an evacuation building flooded with people on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShelterFailureEvent {
+    pub step: u32,
+    /// Seconds elapsed at the start of the step
+    pub time: f64,
+    pub shelter_id: u32,
+    /// Floor reached by the water, 0 for the ground floor
+    pub floor: usize,
+    /// Inundation depth at the shelter in metres
+    pub depth: f64,
+    pub deaths: usize,
 }
 
 impl Model {
-    pub fn step(&mut self, _step: u32, is_tsunami: bool, tsunami_number: usize) {
+    /// Create a model from a loaded grid and its agents
+    pub fn new(grid: Grid, agents: Vec<Agent>) -> Self {
+        Model {
+            grid,
+            agents,
+            dead_agents: 0,
+            dead_agent_types: Vec::new(),
+            shelter_overflows: Vec::new(),
+            shelter_arrivals: Vec::new(),
+            shelter_choice: ShelterChoicePolicy::default(),
+            fragility: FragilityConfig::default(),
+            tsunami_interpolation: FrameInterpolation::default(),
+            clock: SimClock::default(),
+            density: DensityConfig::default(),
+            vehicles: VehicleConfig::default(),
+            parked_vehicles: HashMap::new(),
+            dead_vehicles: VehicleCounts::default(),
+            households: Vec::new(),
+            gathering_time: 0.0,
+            knowledge: KnowledgeConfig::default(),
+            tsunami_envelope: MaxDepthEnvelope::default(),
+            routed_frame: None,
+            shelter_floors: HashMap::new(),
+            stair_allowance: HashMap::new(),
+            shelter_failures: Vec::new(),
+            agent_paths: HashMap::new(),
+            rng: RngStreams::from_entropy(),
+        }
+    }
+
+    /// Reseed every random stream; the same seed and inputs give identical runs
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = RngStreams::new(seed);
+    }
+
+    /// Draw which agents know the route, from the informed share of their
+    /// type. A household knows the route if any of its members does.
+    pub fn assign_route_knowledge(&mut self) {
+        if self.knowledge.everyone_informed() {
+            return;
+        }
+        for agent in &mut self.agents {
+            agent.knowledge = self.knowledge.sample(agent.agent_type, &mut self.rng.knowledge);
+        }
+
+        let informed_households: HashSet<usize> = self
+            .agents
+            .iter()
+            .filter(|agent| agent.knowledge == RouteKnowledge::Informed)
+            .filter_map(|agent| agent.household)
+            .collect();
+        for agent in &mut self.agents {
+            if agent.household.is_some_and(|household| informed_households.contains(&household)) {
+                agent.knowledge = RouteKnowledge::Informed;
+            }
+        }
+    }
+
+    /// Draw which agents drive, from the vehicle mode share. Only agents
+    /// starting on a road can drive, and members of households walk together.
+    /// A driver takes on board pedestrians starting in the same cell, up to
+    /// the vehicle's occupancy; they leave `agents` and travel in the vehicle.
+    pub fn assign_travel_modes(&mut self) {
+        if self.vehicles.mode_share.iter().sum::<f64>() <= 0.0 {
+            return;
+        }
+        let grouped: HashSet<usize> = self
+            .households
+            .iter()
+            .filter(|household| household.members.len() > 1)
+            .flat_map(|household| household.members.iter().copied())
+            .collect();
+        // Agents on their own who could ride along, by cell, in id order
+        let mut riders: HashMap<(u32, u32), Vec<(usize, AgentType)>> = HashMap::new();
+        for agent in &self.agents {
+            if !grouped.contains(&agent.id) {
+                riders
+                    .entry((agent.x, agent.y))
+                    .or_default()
+                    .push((agent.id, agent.agent_type));
+            }
+        }
+
+        let mut boarded: HashSet<usize> = HashSet::new();
+        for agent in &mut self.agents {
+            if !agent.is_on_road || grouped.contains(&agent.id) || boarded.contains(&agent.id) {
+                continue;
+            }
+            if let Some(kind) = self.vehicles.sample_mode(&mut self.rng.travel_mode) {
+                let spec = self.vehicles.spec(kind);
+                let cell = riders.entry((agent.x, agent.y)).or_default();
+                cell.retain(|&(id, _)| id != agent.id);
+                let seats = (spec.occupancy.saturating_sub(1) as usize).min(cell.len());
+                let passengers: Vec<(usize, AgentType)> = cell.drain(..seats).collect();
+                boarded.extend(passengers.iter().map(|&(id, _)| id));
+                agent.drive(kind, spec, passengers.into_iter().map(|(_, agent_type)| agent_type).collect());
+            }
+        }
+
+        for agent in self.agents.iter().filter(|agent| boarded.contains(&agent.id)) {
+            self.grid.remove_agent(agent.x, agent.y, agent.id);
+        }
+        self.agents.retain(|agent| !boarded.contains(&agent.id));
+    }
+
+    /// Draw every agent's departure step: the warning time plus a milling
+    /// delay from the distribution for its type, converted with the clock
+    pub fn assign_departure_times(&mut self, departure: &DepartureConfig, warning_time: f64) {
+        for agent in &mut self.agents {
+            let time = departure.departure_time(agent.agent_type, warning_time, &mut self.rng.departure);
+            agent.departure_step = self.clock.steps_for(time);
+        }
+
+        // A household leaves together once its last member is ready and
+        // everyone has gathered
+        let gathering = self.clock.steps_for(self.gathering_time);
+        let index: HashMap<usize, usize> = self
+            .agents
+            .iter()
+            .enumerate()
+            .map(|(i, agent)| (agent.id, i))
+            .collect();
+        for household in &self.households {
+            let members: Vec<usize> = household
+                .members
+                .iter()
+                .filter_map(|id| index.get(id).copied())
+                .collect();
+            let Some(ready) = members.iter().map(|&i| self.agents[i].departure_step).max() else {
+                continue;
+            };
+            for &i in &members {
+                self.agents[i].departure_step = ready + gathering;
+            }
+        }
+    }
+
+    /// What became of each household's members so far
+    pub fn household_outcomes(&self) -> Vec<HouseholdOutcome> {
+        let living: HashSet<usize> = self.agents.iter().map(|agent| agent.id).collect();
+        let sheltered: HashSet<usize> = self
+            .shelter_arrivals
+            .iter()
+            .map(|arrival| arrival.agent_id)
+            .collect();
+        self.households
+            .iter()
+            .map(|household| HouseholdOutcome::tally(household, &living, &sheltered))
+            .collect()
+    }
+
+    /// Switch the shelter-choice policy, computing per-shelter distance fields
+    /// and assigning targets when the policy needs them
+    pub fn set_shelter_choice(&mut self, policy: ShelterChoicePolicy) {
+        self.shelter_choice = policy;
+
+        if self.shelter_choice.uses_targets() {
+            if self.grid.shelter_distance_fields.is_empty() {
+                self.grid.compute_shelter_distance_fields();
+            }
+        } else {
+            for agent in &mut self.agents {
+                agent.target_shelter = None;
+            }
+        }
+
+        self.assign_shelter_targets();
+    }
+
+    /// Give every evacuating agent without a usable target a shelter chosen by
+    /// the current policy. Agents whose target has filled up are re-assigned.
+    pub fn assign_shelter_targets(&mut self) {
+        if !self.shelter_choice.uses_targets() {
+            return;
+        }
+
+        // Agents already heading to each shelter
+        let mut pending: HashMap<u32, usize> = HashMap::new();
+        for agent in &self.agents {
+            if let Some(target) = agent.target_shelter {
+                if !self.is_in_shelter(agent.x, agent.y) && self.grid.shelter_has_space(target) {
+                    *pending.entry(target).or_insert(0) += 1;
+                }
+            }
+        }
+
+        for i in 0..self.agents.len() {
+            let agent = &self.agents[i];
+            if self.is_in_shelter(agent.x, agent.y)
+                || agent
+                    .target_shelter
+                    .is_some_and(|target| self.grid.shelter_has_space(target))
+            {
+                continue;
+            }
+
+            let target = self
+                .shelter_choice
+                .choose(
+                    &self.grid,
+                    agent.x,
+                    agent.y,
+                    agent.origin_cell(),
+                    &pending,
+                    &mut self.rng.shelter_choice,
+                );
+            if let Some(target) = target {
+                *pending.entry(target).or_insert(0) += 1;
+            }
+            self.agents[i].target_shelter = target;
+        }
+    }
+
+    /// Distance from (x, y) to the agent's target shelter, or to the nearest
+    /// shelter if it has none
+    fn shelter_distance_for(&self, agent: &Agent, x: u32, y: u32) -> Option<u32> {
+        match agent.target_shelter {
+            Some(target) => self.grid.distance_to_shelter_id(target, x, y),
+            None => self.grid.distance_to_shelter[y as usize][x as usize],
+        }
+    }
+
+    /// Whether the agent may step onto (x, y): full shelters turn everyone away
+    /// and agents with a target do not enter other shelters
+    fn can_enter(&self, agent: &Agent, x: u32, y: u32) -> bool {
+        match self.grid.terrain[y as usize][x as usize] {
+            Terrain::Shelter(shelter_id) => {
+                self.grid.is_shelter_entrance(x, y)
+                    && self.shelter_admits(agent, shelter_id)
+                    && agent.target_shelter.is_none_or(|target| target == shelter_id)
+            }
+            // Vehicles stay on the road network
+            Terrain::Road => true,
+            _ => agent.vehicle.is_none(),
+        }
+    }
+
+    pub fn step(&mut self, step: u32, is_tsunami: bool, tsunami_number: usize) {
+        self.step_at(step, is_tsunami, tsunami_number as f64);
+    }
+
+    /// Water depth at every agent's cell at a fractional tsunami frame position
+    fn sample_agent_depths(&mut self, frame_position: f64) -> Vec<f32> {
+        let cells: Vec<(u32, u32)> = self.agents.iter().map(|agent| (agent.x, agent.y)).collect();
+        self.sample_depths(frame_position, &cells)
+    }
+
+    /// Water depth at each cell at a fractional tsunami frame position.
+    /// Each frame is fetched once per call, however many cells there are.
+    fn sample_depths(&mut self, frame_position: f64, cells: &[(u32, u32)]) -> Vec<f32> {
+        let envelope = if self.tsunami_interpolation == FrameInterpolation::MaxSoFar {
+            let through = frame_position.max(0.0).floor() as usize;
+            if let Err(e) = self.tsunami_envelope.advance(self.grid.tsunami(), through) {
+                eprintln!("Error reading tsunami frames: {}", e);
+            }
+            Some(&self.tsunami_envelope)
+        } else {
+            None
+        };
+
+        let sampler = DepthSampler::new(
+            self.grid.tsunami(),
+            frame_position,
+            self.tsunami_interpolation,
+            envelope,
+        );
+        cells.iter().map(|&(x, y)| sampler.depth(x, y)).collect()
+    }
+
+    /// Drown everyone on the floors of evacuation buildings the water has
+    /// reached. A building whose top floor is reached fails: it takes in
+    /// nobody else and routes are recomputed without it. Returns the deaths.
+    fn flood_shelter_floors(&mut self, step: u32, time: f64, frame_position: f64) -> usize {
+        let buildings: Vec<(u32, u32, u32)> = self
+            .grid
+            .shelters
+            .iter()
+            .filter(|&&(_, _, id)| self.grid.shelter_building(id).is_some())
+            .copied()
+            .collect();
+        if buildings.is_empty() {
+            return 0;
+        }
+
+        let cells: Vec<(u32, u32)> = buildings.iter().map(|&(x, y, _)| (x, y)).collect();
+        let depths = self.sample_depths(frame_position, &cells);
+        let mut deepest: HashMap<u32, f64> = HashMap::new();
+        for (&(_, _, shelter_id), &depth) in buildings.iter().zip(&depths) {
+            let entry = deepest.entry(shelter_id).or_insert(0.0);
+            *entry = entry.max(depth as f64);
+        }
+        let mut shelter_ids: Vec<u32> = deepest.keys().copied().collect();
+        shelter_ids.sort_unstable();
+
+        let mut deaths = 0;
+        let mut failed = false;
+        for shelter_id in shelter_ids {
+            let depth = deepest[&shelter_id];
+            let building = self.grid.config.shelter_buildings[&shelter_id].clone();
+            if depth > building.safe_height() {
+                failed |= self.grid.failed_shelters.insert(shelter_id);
+            }
+
+            let mut lost = Vec::new();
+            if let Some(floors) = self.shelter_floors.get_mut(&shelter_id) {
+                for (floor, occupants) in floors.iter_mut().enumerate() {
+                    if occupants.is_empty() || building.floor_is_safe(floor, depth) {
+                        continue;
+                    }
+                    let people: usize = occupants.iter().map(|occupant| occupant.people).sum();
+                    self.shelter_failures.push(ShelterFailureEvent {
+                        step,
+                        time,
+                        shelter_id,
+                        floor,
+                        depth,
+                        deaths: people,
+                    });
+                    lost.append(occupants);
+                }
+            }
+
+            for occupant in lost {
+                deaths += occupant.people;
+                self.remove_sheltered(shelter_id, occupant);
+            }
+        }
+
+        if failed {
+            self.grid.compute_distance_to_shelters();
+            if !self.grid.shelter_distance_fields.is_empty() {
+                self.grid.compute_shelter_distance_fields();
+            }
+        }
+        deaths
+    }
+
+    /// Remove an agent who died on a flooded floor from the shelter and the model
+    fn remove_sheltered(&mut self, shelter_id: u32, occupant: FloorOccupant) {
+        if let Some(entries) = self.grid.shelter_agents.get_mut(&shelter_id) {
+            entries.retain(|&(agent_id, _)| agent_id != occupant.agent_id);
+        }
+        if let Some(i) = self.agents.iter().position(|agent| agent.id == occupant.agent_id) {
+            let agent = self.agents.remove(i);
+            self.grid.remove_agent(agent.x, agent.y, agent.id);
+            self.dead_agent_types.extend(agent.people_types());
+        }
+    }
+
+    /// Whether the stairs of a shelter can take in someone this step
+    fn stairs_free(&self, shelter_id: u32) -> bool {
+        self.stair_allowance
+            .get(&shelter_id)
+            .is_none_or(|&allowance| allowance >= 1.0)
+    }
+
+    /// Advance one step with the hazard sampled at a fractional tsunami frame
+    /// position, interpolated according to `tsunami_interpolation`
+    pub fn step_at(&mut self, step: u32, is_tsunami: bool, frame_position: f64) {
         let mut dead_agents_this_step = 0;
+        self.clock.step = step;
+        let time = self.clock.elapsed();
+
+        // Stairs take in people at their throughput; unused capacity does not
+        // build up beyond one step's worth
+        for (&shelter_id, building) in &self.grid.config.shelter_buildings {
+            if let Some(per_step) = building.stair_capacity_per_step(self.clock.dt) {
+                let allowance = self.stair_allowance.entry(shelter_id).or_insert(0.0);
+                *allowance = (*allowance + per_step).min(per_step.max(1.0));
+            }
+        }
+
+        let cellsize = self.grid.cellsize;
+        for agent in &mut self.agents {
+            // Agents still preparing to leave do not move
+            if step >= agent.departure_step {
+                let crowding = if self.density.is_enabled() {
+                    let density = self.grid.local_density(agent.x, agent.y, self.density.radius);
+                    self.density.diagram.speed_factor(density)
+                } else {
+                    1.0
+                };
+                agent.progress += agent.cells_per_step(cellsize, self.clock.dt) * crowding;
+            }
+        }
+
+        let frame_count = self.grid.tsunami_frame_count();
+        if is_tsunami && frame_count > 0 && frame_position < frame_count as f64 {
+            println!("TSUNAMI IS COMMING ----- {:.2}", frame_position);
 
-        if is_tsunami && !self.grid.tsunami_data.is_empty() && tsunami_number < self.grid.tsunami_data.len() {
-            println!("TSUNAMI IS COMMING ----- {}", tsunami_number);
-            let tsunami_data = self.grid.tsunami_data[tsunami_number].clone();
+            let depths = self.sample_agent_depths(frame_position);
 
             for i in (0..self.agents.len()).rev() {
-                let agent = &self.agents[i];
-                if (agent.y as usize) < tsunami_data.len()
-                    && (agent.x as usize) < tsunami_data[0].len()
-                {
-                    let tsunami_height = tsunami_data[agent.y as usize][agent.x as usize];
-                    if tsunami_height > 0 {
-                        dead_agents_this_step += 1;
-                        self.grid.remove_agent(agent.x, agent.y, i);
+                let (x, y) = (self.agents[i].x, self.agents[i].y);
+                let depth = depths[i] as f64;
+                if depth <= 0.0 {
+                    continue;
+                }
+                // People in an evacuation building live or die with their floor
+                if let Terrain::Shelter(shelter_id) = self.grid.terrain[y as usize][x as usize] {
+                    if self.grid.shelter_building(shelter_id).is_some() {
+                        continue;
+                    }
+                }
+
+                let agent = &mut self.agents[i];
+                let quantile = *agent
+                    .fragility_quantile
+                    .get_or_insert_with(|| self.rng.hazard.gen());
+
+                match self.fragility.outcome(agent.agent_type, depth, quantile) {
+                    HazardOutcome::Dead => {
+                        // Everyone in a vehicle shares the driver's fate
+                        let people = agent.people() as usize;
+                        dead_agents_this_step += people;
+                        self.grid.remove_agent(x, y, agent.id);
                         println!(
-                            "Agent {} mati akibat tsunami pada koordinat ({}, {})",
-                            i, agent.x, agent.y
+                            "Agent {} mati akibat tsunami pada koordinat ({}, {}), kedalaman {:.2} m",
+                            agent.id, x, y, depth
                         );
 
-                        self.dead_agent_types.push(agent.agent_type);
+                        self.dead_agent_types.extend(agent.people_types());
+                        if let Some(vehicle) = &agent.vehicle {
+                            self.dead_vehicles.add(vehicle);
+                        }
                         self.agents.remove(i);
                     }
-                }
-            }
-            println!("Jumlah agen mati pada step ini: {}", dead_agents_this_step);
-
-            // Update tsunami cells if tsunami data exists
-            if tsunami_number < self.grid.tsunami_data.len() {
-                for row in self.grid.tsunami_data[tsunami_number].iter_mut() {
-                    for cell in row.iter_mut() {
-                        if *cell != 0 {
-                            *cell = 10;
-                        }
+                    HazardOutcome::Slowed => {
+                        // Swept off balance: only part of the usual movement this step
+                        agent.progress *= self.fragility.slowed_speed_factor;
                     }
+                    HazardOutcome::Unharmed => {}
                 }
             }
+            dead_agents_this_step += self.flood_shelter_floors(step, time, frame_position);
+            println!("Jumlah agen mati pada step ini: {}", dead_agents_this_step);
         }
 
         self.dead_agents += dead_agents_this_step;
 
-        let mut rng = rand::thread_rng();
+        if is_tsunami && frame_count > 0 {
+            self.reroute_around_flooding(frame_position.max(0.0).floor() as usize);
+        }
+
         let mut agent_order: Vec<usize> = (0..self.agents.len()).collect();
 
-        for agent in &mut self.agents {
-            agent.remaining_steps = agent.speed;
-        }
+        self.assign_shelter_targets();
+        self.retarget_refused_agents();
+        self.update_agent_paths();
+
+        // Moves are planned while the model is borrowed, so the fallback
+        // stream is held locally for the phase and stored back after it
+        let mut fallback_rng = self.rng.fallback_moves.clone();
 
-        for _ in 0..self.agents.iter().map(|a| a.speed).max().unwrap_or(1) {
-            agent_order.shuffle(&mut rng);
-            let mut reserved_cells = HashSet::new();
+        let max_moves = self
+            .agents
+            .iter()
+            .map(|a| a.progress.floor() as u32)
+            .max()
+            .unwrap_or(0);
+
+        for _ in 0..max_moves {
+            agent_order.shuffle(&mut self.rng.agent_order);
+            // Load of every cell, counting both its agents and the moves into
+            // it planned so far in this round
+            let mut occupied = self.cell_loads();
             let mut moves = Vec::new();
+            // Household members sharing a cell move as one group behind a
+            // leader, and only when the slowest of them can
+            let groups = self.household_groups();
+            let following: HashSet<usize> = groups.values().flatten().copied().collect();
 
             for &id in &agent_order {
                 let agent = &self.agents[id];
-                if agent.remaining_steps == 0 || self.is_in_shelter(agent.x, agent.y) {
+                if agent.progress < 1.0
+                    || self.is_in_shelter(agent.x, agent.y)
+                    || following.contains(&id)
+                {
                     continue;
                 }
-                if let Some((nx, ny, fallback)) = self.find_best_move(agent, &reserved_cells) {
-                    reserved_cells.insert((nx, ny));
-                    moves.push((id, nx, ny, fallback));
+                let followers = groups.get(&id).map_or(&[][..], Vec::as_slice);
+                if followers.iter().any(|&f| self.agents[f].progress < 1.0) {
+                    continue;
+                }
+                if let Some((nx, ny, fallback)) =
+                    self.find_best_move(agent, &occupied, &mut fallback_rng)
+                {
+                    // The leader found room; the whole group has to fit
+                    let group_footprint: f64 = followers
+                        .iter()
+                        .map(|&member| self.footprint(&self.agents[member]))
+                        .sum::<f64>()
+                        + self.footprint(agent);
+                    if !self.has_room_for(group_footprint, nx, ny, &occupied) {
+                        continue;
+                    }
+                    for &member in std::iter::once(&id).chain(followers) {
+                        *occupied.entry((nx, ny)).or_insert(0.0) +=
+                            self.footprint(&self.agents[member]);
+                        moves.push((member, nx, ny, fallback));
+                    }
                 }
             }
 
             for &(id, new_x, new_y, fallback) in &moves {
-                let (old_x, old_y) = {
+                // A shelter may have filled up earlier in this batch of moves;
+                // the agent is turned away and stays where it is
+                if let Terrain::Shelter(shelter_id) =
+                    self.grid.terrain[new_y as usize][new_x as usize]
+                {
+                    if !self.shelter_admits(&self.agents[id], shelter_id) {
+                        self.shelter_overflows.push(ShelterOverflowEvent {
+                            step,
+                            time,
+                            shelter_id,
+                            agent_id: self.agents[id].id,
+                            x: new_x,
+                            y: new_y,
+                        });
+                        continue;
+                    }
+                    // The stairs are busy: the agent waits at the door
+                    if !self.stairs_free(shelter_id) {
+                        continue;
+                    }
+                }
+
+                let (old_x, old_y, agent_id) = {
                     let agent = &self.agents[id];
-                    (agent.x, agent.y)
+                    (agent.x, agent.y, agent.id)
                 };
 
-                self.grid.remove_agent(old_x, old_y, id);
+                self.grid.remove_agent(old_x, old_y, agent_id);
 
                 let agent = &mut self.agents[id];
                 let was_on_road = agent.is_on_road;
@@ -95,31 +667,168 @@ impl Model {
                     // println!("Agent {} reached road at ({}, {})", id, new_x, new_y);
                 }
 
+                agent.heading = Some((new_x as i32 - old_x as i32, new_y as i32 - old_y as i32));
                 agent.x = new_x;
                 agent.y = new_y;
 
                 if fallback {
-                    if agent.remaining_steps >= 2 {
-                        agent.remaining_steps -= 2;
-                    } else {
-                        agent.remaining_steps = 0;
-                    }
+                    agent.progress = (agent.progress - 2.0).max(0.0);
                 } else {
-                    agent.remaining_steps -= 1;
+                    agent.progress -= 1.0;
                 }
 
                 let in_shelter = self.is_in_shelter(new_x, new_y);
                 if in_shelter {
-                    self.enter_shelter(id, new_x, new_y);
+                    if self.enter_shelter(id, new_x, new_y) {
+                        if let Terrain::Shelter(shelter_id) =
+                            self.grid.terrain[new_y as usize][new_x as usize]
+                        {
+                            let agent = &self.agents[id];
+                            self.shelter_arrivals.push(ShelterArrivalEvent {
+                                step,
+                                time,
+                                shelter_id,
+                                agent_id: agent.id,
+                                agent_type: agent.agent_type,
+                                vehicle: agent.vehicle.clone(),
+                                knowledge: agent.knowledge,
+                            });
+                        }
+                    }
                     // self.agents.remove(id);
-                    self.grid.remove_agent(new_x, new_y, id);
+                    self.grid.remove_agent(new_x, new_y, agent_id);
+
+                    // Once a shelter fills, re-target everyone to the next
+                    // nearest shelter that still has space
+                    if self.grid.is_full_shelter(new_x, new_y) {
+                        self.grid.compute_distance_to_shelters();
+                    }
                 }
 
-                self.grid.add_agent(new_x, new_y, id);
+                self.grid.add_agent(new_x, new_y, agent_id);
+            }
+        }
+
+        self.rng.fallback_moves = fallback_rng;
+
+        // Whole cells an agent was blocked from using are lost; only the
+        // fraction of a cell carries over to the next step
+        for agent in &mut self.agents {
+            agent.progress = agent.progress.fract();
+        }
+    }
+
+    /// Recompute the shelter routes when a new tsunami frame floods or drains
+    /// cells, if flood routing is enabled. Agents whose target shelter can no
+    /// longer be reached pick a new one.
+    fn reroute_around_flooding(&mut self, frame: usize) {
+        if !self.grid.config.flood_routing.is_enabled() {
+            return;
+        }
+        let frame = frame.min(self.grid.tsunami_frame_count().saturating_sub(1));
+        if self.routed_frame == Some(frame) {
+            return;
+        }
+        self.routed_frame = Some(frame);
+
+        if !self.grid.update_flooding(frame) {
+            return;
+        }
+        // Planned routes may run through water that has just arrived
+        self.agent_paths.clear();
+        for agent in &mut self.agents {
+            if let Some(target) = agent.target_shelter {
+                if self.grid.distance_to_shelter_id(target, agent.x, agent.y).is_none() {
+                    agent.target_shelter = None;
+                }
             }
         }
     }
 
+    /// Whether agents follow individual A* routes rather than the distance field
+    fn uses_astar(&self) -> bool {
+        self.grid.config.path_algorithm == "a_star"
+    }
+
+    /// Keep the A* route of every agent still evacuating, replanning those
+    /// who have left their route or whose shelter has filled up. Agents plan
+    /// once they reach a road, and those heading for a shelter picked by the
+    /// choice policy are not planned.
+    fn update_agent_paths(&mut self) {
+        if !self.uses_astar() {
+            return;
+        }
+
+        let grid = &self.grid;
+        let mut active = HashSet::new();
+        let mut replan = Vec::new();
+        for agent in &self.agents {
+            if self.is_in_shelter(agent.x, agent.y)
+                || agent.knowledge == RouteKnowledge::Local
+                || agent.target_shelter.is_some()
+            {
+                continue;
+            }
+            let valid = self.agent_paths.get(&agent.id).is_some_and(|plan| {
+                grid.shelter_has_space(plan.shelter_id) && plan.path.contains(&(agent.x, agent.y))
+            });
+            if valid {
+                active.insert(agent.id);
+            } else if grid.terrain[agent.y as usize][agent.x as usize] == Terrain::Road {
+                active.insert(agent.id);
+                replan.push((agent.id, agent.x, agent.y));
+            }
+        }
+
+        self.agent_paths.retain(|id, _| active.contains(id));
+        let plans: Vec<(usize, Option<PathPlan>)> = replan
+            .par_iter()
+            .map(|&(id, x, y)| (id, grid.plan_path_astar(x, y)))
+            .collect();
+        for (id, plan) in plans {
+            match plan {
+                Some(plan) => {
+                    self.agent_paths.insert(id, plan);
+                }
+                None => {
+                    self.agent_paths.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Next cell on the agent's A* route, if it can step onto it
+    fn planned_move(&self, agent: &Agent, occupied: &HashMap<(u32, u32), f64>) -> Option<(u32, u32)> {
+        let plan = self.agent_paths.get(&agent.id)?;
+        let position = plan.path.iter().position(|&cell| cell == (agent.x, agent.y))?;
+        let &(nx, ny) = plan.path.get(position + 1)?;
+        (self.has_room(agent, nx, ny, occupied) && self.can_enter(agent, nx, ny)).then_some((nx, ny))
+    }
+
+    /// Plan an explicit A* route to the nearest reachable shelter for every
+    /// living agent, keyed by agent id. Agents with no reachable shelter are omitted.
+    pub fn plan_agent_paths(&self) -> HashMap<usize, PathPlan> {
+        self.agents
+            .par_iter()
+            .filter(|agent| agent.is_alive)
+            .filter_map(|agent| {
+                self.grid
+                    .plan_path_astar(agent.x, agent.y)
+                    .map(|plan| (agent.id, plan))
+            })
+            .collect()
+    }
+
+    /// The living agent with the given id. Agents are created in id order and
+    /// only ever removed, so `agents` is normally sorted by id; agents placed
+    /// by hand out of order are found by a scan.
+    pub fn agent_by_id(&self, id: usize) -> Option<&Agent> {
+        match self.agents.binary_search_by_key(&id, |agent| agent.id) {
+            Ok(index) => Some(&self.agents[index]),
+            Err(_) => self.agents.iter().find(|agent| agent.id == id),
+        }
+    }
+
     pub fn is_in_shelter(&self, x: u32, y: u32) -> bool {
         matches!(
             self.grid.terrain[y as usize][x as usize],
@@ -127,22 +836,324 @@ impl Model {
         )
     }
 
-    pub fn enter_shelter(&mut self, agent_id: usize, x: u32, y: u32) {
+    /// Admit the agent at `index` in `agents` to the shelter at (x, y),
+    /// returning false if it is full. A vehicle is parked and all of its
+    /// occupants are admitted.
+    pub fn enter_shelter(&mut self, index: usize, x: u32, y: u32) -> bool {
         if let Terrain::Shelter(shelter_id) = self.grid.terrain[y as usize][x as usize] {
-            let agent = &self.agents[agent_id];
-            self.grid
-                .add_to_shelter(shelter_id, agent_id, agent.agent_type);
+            let agent = &self.agents[index];
+            if !self.shelter_admits(agent, shelter_id) {
+                return false;
+            }
+            for agent_type in agent.people_types() {
+                self.grid.add_to_shelter(shelter_id, agent.id, agent_type);
+            }
+            if let Some(building) = self.grid.shelter_building(shelter_id) {
+                // Everyone climbs together to the highest floor with room for them all
+                let people = agent.people() as usize;
+                let floors = self
+                    .shelter_floors
+                    .entry(shelter_id)
+                    .or_insert_with(|| vec![Vec::new(); building.floor_capacities.len()]);
+                let floor = (0..floors.len()).rev().find(|&floor| {
+                    floors[floor]
+                        .iter()
+                        .map(|occupant| occupant.people)
+                        .sum::<usize>()
+                        + people
+                        <= building.floor_capacities[floor] as usize
+                });
+                // People beyond the floor capacities stay on the ground floor
+                if let Some(occupants) = floor
+                    .or((!floors.is_empty()).then_some(0))
+                    .map(|floor| &mut floors[floor])
+                {
+                    occupants.push(FloorOccupant {
+                        agent_id: agent.id,
+                        people,
+                    });
+                }
+                if let Some(allowance) = self.stair_allowance.get_mut(&shelter_id) {
+                    *allowance -= people as f64;
+                }
+            }
+            if let Some(vehicle) = &agent.vehicle {
+                self.parked_vehicles
+                    .entry(shelter_id)
+                    .or_default()
+                    .add(vehicle);
+            }
+            true
+        } else {
+            false
         }
     }
 
+    /// Whether a shelter is open and accessible to the agent, has room for
+    /// everyone it stands for and, for a vehicle, a free parking space
+    fn shelter_admits(&self, agent: &Agent, shelter_id: u32) -> bool {
+        if !self.shelter_open_to(agent, shelter_id) {
+            return false;
+        }
+        let parking = agent.vehicle.is_none()
+            || self.vehicles.parking_capacity(shelter_id).is_none_or(|capacity| {
+                self.parked_vehicles
+                    .get(&shelter_id)
+                    .map_or(0, |parked| parked.total())
+                    < capacity
+            });
+        parking && self.grid.shelter_has_space_for(shelter_id, agent.people() as usize)
+    }
+
+    /// Whether a shelter has opened and is accessible to the agent, whatever room it has left
+    fn shelter_open_to(&self, agent: &Agent, shelter_id: u32) -> bool {
+        self.grid.shelter_info(shelter_id).is_none_or(|info| {
+            info.opening_time <= self.clock.elapsed()
+                && (info.elder_accessible || agent.agent_type != AgentType::Elder)
+        })
+    }
+
+    /// Send agents a shelter turns away, because it has not opened yet or is
+    /// not accessible to them, to the nearest shelter with room that lets
+    /// them in: agents targeting such a shelter, and agents without a target
+    /// standing at its door. Left without a target if there is none.
+    fn retarget_refused_agents(&mut self) {
+        let dirs = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+        let refused: Vec<usize> = (0..self.agents.len())
+            .filter(|&i| {
+                let agent = &self.agents[i];
+                if self.is_in_shelter(agent.x, agent.y) {
+                    return false;
+                }
+                match agent.target_shelter {
+                    Some(target) => !self.shelter_open_to(agent, target),
+                    None => dirs.iter().any(|&(dx, dy)| {
+                        let nx = agent.x as i32 + dx;
+                        let ny = agent.y as i32 + dy;
+                        if nx < 0 || ny < 0 || nx >= self.grid.width as i32 || ny >= self.grid.height as i32 {
+                            return false;
+                        }
+                        let (nx, ny) = (nx as u32, ny as u32);
+                        matches!(
+                            self.grid.terrain[ny as usize][nx as usize],
+                            Terrain::Shelter(id) if self.grid.is_shelter_entrance(nx, ny) && !self.shelter_open_to(agent, id)
+                        )
+                    }),
+                }
+            })
+            .collect();
+        if refused.is_empty() {
+            return;
+        }
+
+        if self.grid.shelter_distance_fields.is_empty() {
+            self.grid.compute_shelter_distance_fields();
+        }
+        let shelter_ids = self.grid.shelter_ids();
+        for i in refused {
+            let agent = &self.agents[i];
+            let target = shelter_ids
+                .iter()
+                .filter(|&&id| self.shelter_open_to(agent, id) && self.grid.shelter_has_space(id))
+                .filter_map(|&id| self.grid.distance_to_shelter_id(id, agent.x, agent.y).map(|d| (d, id)))
+                .min()
+                .map(|(_, id)| id);
+            self.agents[i].target_shelter = target;
+        }
+    }
+
+    /// Household members sharing a cell outside the shelters: the index of
+    /// the first member, which leads the group, mapped to the indices of the others
+    fn household_groups(&self) -> HashMap<usize, Vec<usize>> {
+        let mut cells: HashMap<(usize, u32, u32), Vec<usize>> = HashMap::new();
+        for (i, agent) in self.agents.iter().enumerate() {
+            if let Some(household) = agent.household {
+                if !self.is_in_shelter(agent.x, agent.y) {
+                    cells.entry((household, agent.x, agent.y)).or_default().push(i);
+                }
+            }
+        }
+        cells
+            .into_values()
+            .filter(|members| members.len() > 1)
+            .map(|mut members| {
+                let leader = members.remove(0);
+                (leader, members)
+            })
+            .collect()
+    }
+
+    /// Share of a cell's capacity the agent takes up: one for a pedestrian,
+    /// the vehicle footprint for a driver
+    fn footprint(&self, agent: &Agent) -> f64 {
+        agent
+            .vehicle
+            .as_ref()
+            .map_or(1.0, |vehicle| self.vehicles.spec(vehicle.kind).footprint)
+    }
+
+    /// Load of every occupied cell outside the shelters
+    fn cell_loads(&self) -> HashMap<(u32, u32), f64> {
+        let mut loads = HashMap::new();
+        for agent in &self.agents {
+            if !self.is_in_shelter(agent.x, agent.y) {
+                *loads.entry((agent.x, agent.y)).or_insert(0.0) += self.footprint(agent);
+            }
+        }
+        loads
+    }
+
+    /// Agents that fit in cell (x, y) at once: the per-terrain capacity of the
+    /// grid if configured, otherwise the uniform capacity of the density model
+    pub fn cell_capacity(&self, x: u32, y: u32) -> usize {
+        self.grid
+            .cell_capacity(x, y)
+            .unwrap_or_else(|| self.density.cell_capacity(self.grid.cellsize))
+    }
+
+    /// Whether the agent fits in (x, y) given the cell's current load. An
+    /// empty cell takes any one agent, however large its footprint.
+    fn has_room(&self, agent: &Agent, x: u32, y: u32, occupied: &HashMap<(u32, u32), f64>) -> bool {
+        self.has_room_for(self.footprint(agent), x, y, occupied)
+    }
+
+    /// Whether a load of `footprint` fits in (x, y) given the cell's current
+    /// load. An empty cell takes any load.
+    fn has_room_for(&self, footprint: f64, x: u32, y: u32, occupied: &HashMap<(u32, u32), f64>) -> bool {
+        let load = occupied.get(&(x, y)).copied().unwrap_or(0.0);
+        load <= 0.0 || load + footprint <= self.cell_capacity(x, y) as f64
+    }
+
+    /// Move of an agent that does not know the route: towards a shelter in
+    /// sight, else the way the nearest sign in sight points, then the way
+    /// informed neighbours are heading, then the way the crowd is heading,
+    /// then straight on. None leaves it to a fallback move.
+    fn find_local_move(&self, agent: &Agent, occupied: &HashMap<(u32, u32), f64>) -> Option<(u32, u32)> {
+        let dirs = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+        let options: Vec<((i32, i32), u32, u32)> = dirs
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let nx = agent.x as i32 + dx;
+                let ny = agent.y as i32 + dy;
+                if nx < 0 || ny < 0 || nx >= self.grid.width as i32 || ny >= self.grid.height as i32 {
+                    return None;
+                }
+                let (nx, ny) = (nx as u32, ny as u32);
+                let walkable = matches!(
+                    self.grid.terrain[ny as usize][nx as usize],
+                    Terrain::Road | Terrain::Shelter(_)
+                );
+                (walkable && self.has_room(agent, nx, ny, occupied) && self.can_enter(agent, nx, ny))
+                    .then_some(((dx, dy), nx, ny))
+            })
+            .collect();
+        if options.is_empty() {
+            return None;
+        }
+
+        let radius = self.knowledge.perception_radius;
+        let in_sight = |x: u32, y: u32| x.abs_diff(agent.x) <= radius && y.abs_diff(agent.y) <= radius;
+
+        // A shelter in sight that would take the agent
+        let visible_shelters: Vec<(u32, u32)> = self
+            .grid
+            .shelters
+            .iter()
+            .filter(|&&(x, y, id)| in_sight(x, y) && self.can_enter(agent, x, y) && self.grid.shelter_has_space(id))
+            .map(|&(x, y, _)| (x, y))
+            .collect();
+        let distance_to_visible = |x: u32, y: u32| {
+            visible_shelters
+                .iter()
+                .map(|&(sx, sy)| x.abs_diff(sx) + y.abs_diff(sy))
+                .min()
+        };
+        if let Some(current) = distance_to_visible(agent.x, agent.y) {
+            let closer = options
+                .iter()
+                .filter_map(|&(_, nx, ny)| distance_to_visible(nx, ny).map(|d| (d, nx, ny)))
+                .filter(|&(d, _, _)| d < current)
+                .min_by_key(|&(d, _, _)| d);
+            if let Some((_, nx, ny)) = closer {
+                return Some((nx, ny));
+            }
+        }
+
+        // The nearest sign in sight; on a grid without diagonal moves a
+        // diagonal sign is followed along either of its components
+        let nearest_sign = self
+            .grid
+            .signs
+            .iter()
+            .filter(|&&(x, y, _)| in_sight(x, y))
+            .min_by_key(|&&(x, y, _)| x.abs_diff(agent.x) + y.abs_diff(agent.y));
+        if let Some(&(_, _, heading)) = nearest_sign {
+            let (hx, hy) = heading.offset();
+            let along = options
+                .iter()
+                .filter(|&&((dx, dy), _, _)| dx * hx + dy * hy > 0)
+                .max_by_key(|&&((dx, dy), _, _)| dx * hx + dy * hy);
+            if let Some(&(_, nx, ny)) = along {
+                return Some((nx, ny));
+            }
+        }
+
+        // Headings of the other agents in sight, informed ones first
+        let mut informed_headings = Vec::new();
+        let mut crowd_headings = Vec::new();
+        let (x0, y0) = (agent.x.saturating_sub(radius), agent.y.saturating_sub(radius));
+        let x1 = (agent.x + radius).min(self.grid.width - 1);
+        let y1 = (agent.y + radius).min(self.grid.height - 1);
+        for cy in y0..=y1 {
+            for cx in x0..=x1 {
+                for &id in &self.grid.agents_in_cell[cy as usize][cx as usize] {
+                    let Some(other) = self.agent_by_id(id) else {
+                        continue;
+                    };
+                    if other.id == agent.id || (other.x, other.y) != (cx, cy) {
+                        continue;
+                    }
+                    if let Some(heading) = other.heading {
+                        if other.knowledge == RouteKnowledge::Informed {
+                            informed_headings.push(heading);
+                        }
+                        crowd_headings.push(heading);
+                    }
+                }
+            }
+        }
+
+        let follow = |headings: &[(i32, i32)]| {
+            options
+                .iter()
+                .map(|&(dir, nx, ny)| (headings.iter().filter(|&&h| h == dir).count(), nx, ny))
+                .filter(|&(count, _, _)| count > 0)
+                .max_by_key(|&(count, _, _)| count)
+                .map(|(_, nx, ny)| (nx, ny))
+        };
+        follow(&informed_headings)
+            .or_else(|| follow(&crowd_headings))
+            .or_else(|| {
+                options
+                    .iter()
+                    .find(|&&(dir, _, _)| Some(dir) == agent.heading)
+                    .map(|&(_, nx, ny)| (nx, ny))
+            })
+    }
+
     fn find_best_move(
         &self,
         agent: &Agent,
-        reserved: &HashSet<(u32, u32)>,
+        occupied: &HashMap<(u32, u32), f64>,
+        rng: &mut SimRng,
     ) -> Option<(u32, u32, bool)> {
         let dirs = [(0, 1), (0, -1), (1, 0), (-1, 0)];
         let mut candidates = Vec::new();
 
+        if let Some((nx, ny)) = self.planned_move(agent, occupied) {
+            return Some((nx, ny, false));
+        }
+
         if self.grid.terrain[agent.y as usize][agent.x as usize] != Terrain::Road {
             if let Some(current_dist) =
                 self.grid.distance_to_road[agent.y as usize][agent.x as usize]
@@ -159,8 +1170,7 @@ impl Model {
                         let nx = nx as u32;
                         let ny = ny as u32;
 
-                        if !reserved.contains(&(nx, ny))
-                            && self.grid.agents_in_cell[ny as usize][nx as usize].is_empty()
+                        if self.has_room(agent, nx, ny, occupied) && self.can_enter(agent, nx, ny)
                         {
                             if let Some(new_dist) =
                                 self.grid.distance_to_road[ny as usize][nx as usize]
@@ -179,6 +1189,10 @@ impl Model {
                 let (_, nx, ny) = candidates[0];
                 return Some((nx, ny, false));
             }
+        } else if agent.knowledge == RouteKnowledge::Local {
+            if let Some((nx, ny)) = self.find_local_move(agent, occupied) {
+                return Some((nx, ny, false));
+            }
         } else if self.grid.terrain[agent.y as usize][agent.x as usize] == Terrain::Road {
             for &(dx, dy) in &dirs {
                 let nx = agent.x as i32 + dx;
@@ -192,7 +1206,8 @@ impl Model {
                     if matches!(
                         self.grid.terrain[ny as usize][nx as usize],
                         Terrain::Shelter(_)
-                    ) && !reserved.contains(&(nx, ny))
+                    ) && occupied.get(&(nx, ny)).copied().unwrap_or(0.0) < self.cell_capacity(nx, ny) as f64
+                        && self.can_enter(agent, nx, ny)
                     {
                         return Some((nx, ny, false));
                     }
@@ -202,11 +1217,10 @@ impl Model {
                             self.grid.terrain[ny as usize][nx as usize],
                             Terrain::Shelter(_)
                         ))
-                        && !reserved.contains(&(nx, ny))
-                        && self.grid.agents_in_cell[ny as usize][nx as usize].is_empty()
+                        && self.has_room(agent, nx, ny, occupied)
+                        && self.can_enter(agent, nx, ny)
                     {
-                        if let Some(dist) = self.grid.distance_to_shelter[ny as usize][nx as usize]
-                        {
+                        if let Some(dist) = self.shelter_distance_for(agent, nx, ny) {
                             candidates.push((dist, nx, ny));
                         }
                     }
@@ -229,8 +1243,7 @@ impl Model {
                 {
                     let nx = nx as u32;
                     let ny = ny as u32;
-                    if !reserved.contains(&(nx, ny))
-                        && self.grid.agents_in_cell[ny as usize][nx as usize].is_empty()
+                    if self.has_room(agent, nx, ny, occupied) && self.can_enter(agent, nx, ny)
                     {
                         Some((nx, ny))
                     } else {
@@ -243,8 +1256,7 @@ impl Model {
             .collect();
 
         if !fallback_moves.is_empty() {
-            let mut rng = rand::thread_rng();
-            let chosen = fallback_moves.choose(&mut rng).unwrap();
+            let chosen = fallback_moves.choose(rng).unwrap();
             Some((chosen.0, chosen.1, true))
         } else {
             None
@@ -261,10 +1273,10 @@ impl Model {
         let mut shelter_counts: HashMap<String, ShelterAgentCounts> = HashMap::new();
 
         for (&shelter_id, agents) in &self.grid.shelter_agents {
-            let shelter_key = format!("shelter_{}", shelter_id);
+            let shelter_key = self.grid.shelter_label(shelter_id);
             let counts = shelter_counts
                 .entry(shelter_key)
-                .or_insert(ShelterAgentCounts::new());
+                .or_default();
 
             for &(_, agent_type) in agents {
                 match agent_type {
@@ -296,6 +1308,44 @@ impl Model {
             "death_json_counter": death_json_counter,
             "shelter_json_counter": shelter_json_counter,
             "shelter_agent_types": current_shelter_data,
+            "shelter_overflow_events": self.shelter_overflows,
+            "shelter_vehicles": self
+                .parked_vehicles
+                .iter()
+                .map(|(&id, counts)| (self.grid.shelter_label(id), counts.clone()))
+                .collect::<HashMap<String, VehicleCounts>>(),
+            "shelters": self
+                .grid
+                .shelter_ids()
+                .into_iter()
+                .map(|id| json!({
+                    "id": id,
+                    "label": self.grid.shelter_label(id),
+                    "info": self.grid.shelter_info(id),
+                    "capacity": self.grid.shelter_capacity(id),
+                    "occupancy": self.grid.shelter_occupancy(id),
+                }))
+                .collect::<Vec<_>>(),
+            "dead_vehicles": self.dead_vehicles,
+            "household_outcomes": self.household_outcomes(),
+            "shelter_failures": self.shelter_failures,
+            "shelter_floors": self
+                .shelter_floors
+                .iter()
+                .map(|(id, floors)| {
+                    let people: Vec<usize> = floors
+                        .iter()
+                        .map(|occupants| occupants.iter().map(|occupant| occupant.people).sum())
+                        .collect();
+                    (self.grid.shelter_label(*id), people)
+                })
+                .collect::<HashMap<String, Vec<usize>>>(),
+            "sheltered_by_knowledge": {
+                "informed": self.shelter_arrivals.iter().filter(|a| a.knowledge == RouteKnowledge::Informed).map(ShelterArrivalEvent::people).sum::<usize>(),
+                "local": self.shelter_arrivals.iter().filter(|a| a.knowledge == RouteKnowledge::Local).map(ShelterArrivalEvent::people).sum::<usize>(),
+            },
+            "seed": self.rng.seed(),
+            "dt": self.clock.dt,
         });
 
         let file = File::create(filename)?;
@@ -313,6 +1363,12 @@ pub struct ShelterAgentCounts {
     pub elder: u32,
 }
 
+impl Default for ShelterAgentCounts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ShelterAgentCounts {
     pub fn new() -> Self {
         ShelterAgentCounts {
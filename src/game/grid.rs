@@ -1,7 +1,14 @@
 use crate::game::agent::AgentType;
+use crate::game::shelter::{ShelterBuilding, ShelterInfo};
 use crate::game::State;
+use crate::tsunami::{
+    DepthSampler, FrameInterpolation, FrameRef, MaxDepthEnvelope, TsunamiSource, TsunamiSourceHandle,
+};
+use std::ops::Deref;
+use std::sync::Arc;
+use rayon::prelude::*;
 use std::collections::BinaryHeap;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use serde::{Serialize, Deserialize};
 
@@ -18,6 +25,64 @@ pub enum Terrain {
     Custom(f64),
 }
 
+/// Direction an evacuation sign points in. North is up the grid, towards row 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SignHeading {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl SignHeading {
+    /// Parse the heading part of a sign token, e.g. "NE" in `sNE`
+    pub fn parse(heading: &str) -> Option<Self> {
+        match heading.to_ascii_uppercase().as_str() {
+            "N" => Some(SignHeading::N),
+            "NE" => Some(SignHeading::NE),
+            "E" => Some(SignHeading::E),
+            "SE" => Some(SignHeading::SE),
+            "S" => Some(SignHeading::S),
+            "SW" => Some(SignHeading::SW),
+            "W" => Some(SignHeading::W),
+            "NW" => Some(SignHeading::NW),
+            _ => None,
+        }
+    }
+
+    /// Grid offset (dx, dy) of one step in this direction
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            SignHeading::N => (0, -1),
+            SignHeading::NE => (1, -1),
+            SignHeading::E => (1, 0),
+            SignHeading::SE => (1, 1),
+            SignHeading::S => (0, 1),
+            SignHeading::SW => (-1, 1),
+            SignHeading::W => (-1, 0),
+            SignHeading::NW => (-1, -1),
+        }
+    }
+
+    /// Compass bearing in degrees clockwise from north
+    pub fn bearing(&self) -> f64 {
+        match self {
+            SignHeading::N => 0.0,
+            SignHeading::NE => 45.0,
+            SignHeading::E => 90.0,
+            SignHeading::SE => 135.0,
+            SignHeading::S => 180.0,
+            SignHeading::SW => 225.0,
+            SignHeading::W => 270.0,
+            SignHeading::NW => 315.0,
+        }
+    }
+}
+
 /// Configuration for the simulation grid
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GridConfig {
@@ -29,6 +94,88 @@ pub struct GridConfig {
     pub shelter_capacity: i32,
     /// Path planning algorithm to use ("dijkstra", "a_star", "bfs")
     pub path_algorithm: String,
+    /// Per-shelter capacity overrides keyed by shelter ID (-1 for unlimited)
+    #[serde(default)]
+    pub shelter_capacities: HashMap<u32, i32>,
+    /// Floors, elevation and stair throughput of shelters that are evacuation
+    /// buildings or high ground, keyed by shelter ID. Their capacity is the
+    /// sum of their floors unless overridden in `shelter_capacities`.
+    #[serde(default)]
+    pub shelter_buildings: HashMap<u32, ShelterBuilding>,
+    /// Names, kinds, capacities, opening times and accessibility of the
+    /// shelters, keyed by shelter ID (see `Grid::attach_shelter_catalogue`)
+    #[serde(default)]
+    pub shelter_catalogue: HashMap<u32, ShelterInfo>,
+    /// Highest pedestrian density per terrain type, from which the number of
+    /// agents a cell holds is derived (default: none, one agent per cell)
+    #[serde(default)]
+    pub cell_densities: Option<TerrainDensities>,
+    /// How flooded cells are treated when shelter routes are recomputed after
+    /// a new tsunami frame (default: water is ignored by routing)
+    #[serde(default)]
+    pub flood_routing: FloodRouting,
+}
+
+/// How shelter routing treats cells under water
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum FloodRouting {
+    /// Routes are computed once at load and never consider the water
+    #[default]
+    Ignore,
+    /// Cells deeper than `min_depth` metres are cut out of the routes
+    Impassable { min_depth: f64 },
+    /// Cells deeper than `min_depth` metres cost `cost` extra to leave
+    Penalty { min_depth: f64, cost: u32 },
+}
+
+/// Cost of leaving a cell cut out of the routes by `FloodRouting::Impassable`.
+/// Such cells are never crossed, but agents caught in them still get a way out.
+const IMPASSABLE_FLOOD_COST: u32 = 1_000_000;
+
+impl FloodRouting {
+    /// Whether routes are recomputed as the water spreads
+    pub fn is_enabled(&self) -> bool {
+        *self != FloodRouting::Ignore
+    }
+
+    /// Whether a cell at this depth counts as flooded
+    pub fn is_flooded(&self, depth: f64) -> bool {
+        match *self {
+            FloodRouting::Ignore => false,
+            FloodRouting::Impassable { min_depth } | FloodRouting::Penalty { min_depth, .. } => depth > min_depth,
+        }
+    }
+}
+
+/// Highest pedestrian density per terrain type, in persons per square metre
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerrainDensities {
+    pub road: f64,
+    pub shelter: f64,
+    pub custom: f64,
+    pub blocked: f64,
+}
+
+impl TerrainDensities {
+    /// The same density on every terrain type
+    pub fn uniform(density: f64) -> Self {
+        TerrainDensities {
+            road: density,
+            shelter: density,
+            custom: density,
+            blocked: density,
+        }
+    }
+
+    /// Density for a terrain type
+    pub fn for_terrain(&self, terrain: Terrain) -> f64 {
+        match terrain {
+            Terrain::Road => self.road,
+            Terrain::Shelter(_) => self.shelter,
+            Terrain::Custom(_) => self.custom,
+            Terrain::Blocked => self.blocked,
+        }
+    }
 }
 
 impl Default for GridConfig {
@@ -38,10 +185,79 @@ impl Default for GridConfig {
             allow_diagonal: false,
             shelter_capacity: -1,
             path_algorithm: "dijkstra".to_string(),
+            shelter_capacities: HashMap::new(),
+            shelter_buildings: HashMap::new(),
+            shelter_catalogue: HashMap::new(),
+            cell_densities: None,
+            flood_routing: FloodRouting::Ignore,
+        }
+    }
+}
+
+/// Route to a shelter produced by the per-agent A* planner
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PathPlan {
+    /// Cells visited from the start cell to the shelter cell, inclusive
+    pub path: Vec<(u32, u32)>,
+    /// Path cost in the same units as `Grid::distance_to_shelter`
+    pub cost: u32,
+    /// Geometric path length in real-world units (diagonal steps count √2 × cellsize)
+    pub length_m: f64,
+    /// ID of the shelter the path ends at
+    pub shelter_id: u32,
+    /// Number of cells expanded by the search, a measure of planning cost
+    pub nodes_expanded: usize,
+}
+
+/// Designated shelter entrance cells (x, y, shelter_id), indexed so that
+/// entrance checks in the movement and routing loops do not scan the list.
+/// Serialized as the plain list; the index is rebuilt when it is read back.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(u32, u32, u32)>", into = "Vec<(u32, u32, u32)>")]
+pub struct ShelterEntrances {
+    entrances: Vec<(u32, u32, u32)>,
+    /// Entrance cells (x, y, shelter_id)
+    cells: HashSet<(u32, u32, u32)>,
+    /// Shelters with designated entrances
+    shelters: HashSet<u32>,
+}
+
+impl ShelterEntrances {
+    /// Whether (x, y) is a designated entrance of the shelter
+    pub fn contains(&self, x: u32, y: u32, shelter_id: u32) -> bool {
+        self.cells.contains(&(x, y, shelter_id))
+    }
+
+    /// Whether the shelter has designated entrances
+    pub fn has_entrances(&self, shelter_id: u32) -> bool {
+        self.shelters.contains(&shelter_id)
+    }
+}
+
+impl From<Vec<(u32, u32, u32)>> for ShelterEntrances {
+    fn from(entrances: Vec<(u32, u32, u32)>) -> Self {
+        ShelterEntrances {
+            cells: entrances.iter().copied().collect(),
+            shelters: entrances.iter().map(|&(_, _, id)| id).collect(),
+            entrances,
         }
     }
 }
 
+impl From<ShelterEntrances> for Vec<(u32, u32, u32)> {
+    fn from(entrances: ShelterEntrances) -> Self {
+        entrances.entrances
+    }
+}
+
+impl Deref for ShelterEntrances {
+    type Target = [(u32, u32, u32)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.entrances
+    }
+}
+
 /// Represents the simulation grid
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Grid {
@@ -59,18 +275,41 @@ pub struct Grid {
     pub terrain: Vec<Vec<Terrain>>,
     /// List of shelter locations (x, y, shelter_id)
     pub shelters: Vec<(u32, u32, u32)>,
-    /// List of agents in each cell
+    /// Designated entrance cells (x, y, shelter_id). The cells of a shelter
+    /// form one region; a shelter without entrances can be entered at any of
+    /// its cells, one with entrances only through them.
+    #[serde(default)]
+    pub shelter_entrances: ShelterEntrances,
+    /// Evacuation signs (x, y, heading), each standing on a road cell
+    #[serde(default)]
+    pub signs: Vec<(u32, u32, SignHeading)>,
+    /// Ids of the agents in each cell
     pub agents_in_cell: Vec<Vec<Vec<usize>>>,
     /// Precomputed distances to nearest road
     pub distance_to_road: Vec<Vec<Option<u32>>>,
     /// Precomputed distances to nearest shelter
     pub distance_to_shelter: Vec<Vec<Option<u32>>>,
-    /// Agents in each shelter
+    /// Precomputed distances to each shelter, keyed by shelter ID
+    /// (only filled when a shelter-choice policy needs them)
+    #[serde(default)]
+    pub shelter_distance_fields: HashMap<u32, Vec<Vec<Option<u32>>>>,
+    /// Cells under water in the latest tsunami frame applied to routing
+    /// (empty until flood routing first runs)
+    #[serde(default)]
+    pub flooded: Vec<Vec<bool>>,
+    /// Shelters whose top floor has been flooded; they take in nobody
+    #[serde(default)]
+    pub failed_shelters: HashSet<u32>,
+    /// Ids and types of the agents in each shelter, one entry per person
     pub shelter_agents: HashMap<u32, Vec<(usize, AgentType)>>,
     /// Population data for each cell
     pub population: Vec<Vec<u32>>,
-    /// Tsunami propagation data
-    pub tsunami_data: Vec<Vec<Vec<u32>>>,
+    /// Tsunami inundation depth in metres, one raster per frame
+    pub tsunami_data: Vec<Vec<Vec<f32>>>,
+    /// Frame source used instead of `tsunami_data` when set, e.g. frames
+    /// streamed from disk
+    #[serde(skip)]
+    pub tsunami_source: TsunamiSourceHandle,
     /// Number of rows (same as height)
     pub nrow: u32,
     /// Number of columns (same as width)
@@ -99,25 +338,282 @@ impl Grid {
         }
     }
 
-    pub fn add_to_shelter(&mut self, shelter_id: u32, agent_id: usize, agent_type: AgentType) {
+    /// Add an agent to a shelter, returning false if the shelter is already full
+    pub fn add_to_shelter(&mut self, shelter_id: u32, agent_id: usize, agent_type: AgentType) -> bool {
+        if !self.shelter_has_space(shelter_id) {
+            return false;
+        }
+
         self.shelter_agents
             .entry(shelter_id)
-            .or_insert(Vec::new())
+            .or_default()
             .push((agent_id, agent_type));
 
         // remove agent from grid
+        true
+    }
+
+    /// Capacity of a shelter, using the per-shelter override if present, then
+    /// its catalogue entry, then the floors of its building (None = unlimited)
+    pub fn shelter_capacity(&self, shelter_id: u32) -> Option<u32> {
+        let capacity = match self.config.shelter_capacities.get(&shelter_id) {
+            Some(&capacity) => capacity,
+            None => {
+                if let Some(capacity) = self.shelter_info(shelter_id).and_then(|info| info.capacity) {
+                    return Some(capacity);
+                }
+                match self.shelter_building(shelter_id) {
+                    Some(building) => return Some(building.capacity()),
+                    None => self.config.shelter_capacity,
+                }
+            }
+        };
+
+        if capacity < 0 {
+            None
+        } else {
+            Some(capacity as u32)
+        }
+    }
+
+    /// Number of agents currently inside a shelter
+    pub fn shelter_occupancy(&self, shelter_id: u32) -> usize {
+        self.shelter_agents.get(&shelter_id).map_or(0, |agents| agents.len())
+    }
+
+    /// Whether a shelter can still accept agents
+    pub fn shelter_has_space(&self, shelter_id: u32) -> bool {
+        self.shelter_has_space_for(shelter_id, 1)
+    }
+
+    /// Catalogue entry of a shelter, if a catalogue is attached
+    pub fn shelter_info(&self, shelter_id: u32) -> Option<&ShelterInfo> {
+        self.config.shelter_catalogue.get(&shelter_id)
+    }
+
+    /// Name of a shelter for reports: its catalogue name, or `shelter_<id>`
+    pub fn shelter_label(&self, shelter_id: u32) -> String {
+        self.shelter_info(shelter_id)
+            .map_or_else(|| format!("shelter_{}", shelter_id), |info| info.name.clone())
+    }
+
+    /// Join a shelter catalogue to the shelters of the grid. Every shelter ID
+    /// in the grid must have exactly one entry and every entry a shelter.
+    pub fn attach_shelter_catalogue(&mut self, entries: Vec<ShelterInfo>) -> std::io::Result<()> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+        let mut catalogue = HashMap::new();
+        for entry in entries {
+            let id = entry.id;
+            if catalogue.insert(id, entry).is_some() {
+                return Err(invalid(format!("Shelter {} appears more than once in the catalogue", id)));
+            }
+        }
+
+        let grid_ids = self.shelter_ids();
+        let mut unknown: Vec<u32> = catalogue
+            .keys()
+            .filter(|id| grid_ids.binary_search(id).is_err())
+            .copied()
+            .collect();
+        unknown.sort_unstable();
+        let missing: Vec<u32> = grid_ids
+            .iter()
+            .filter(|id| !catalogue.contains_key(id))
+            .copied()
+            .collect();
+        if !unknown.is_empty() || !missing.is_empty() {
+            return Err(invalid(format!(
+                "Shelter catalogue does not match the grid: not in the grid {:?}, not in the catalogue {:?}",
+                unknown, missing
+            )));
+        }
+
+        self.config.shelter_catalogue = catalogue;
+        Ok(())
+    }
+
+    /// Building behind a shelter, if it is configured as one
+    pub fn shelter_building(&self, shelter_id: u32) -> Option<&ShelterBuilding> {
+        self.config.shelter_buildings.get(&shelter_id)
+    }
+
+    /// Whether a shelter can still accept `people` more agents
+    pub fn shelter_has_space_for(&self, shelter_id: u32, people: usize) -> bool {
+        !self.failed_shelters.contains(&shelter_id)
+            && self.shelter_capacity(shelter_id)
+            .is_none_or(|capacity| self.shelter_occupancy(shelter_id) + people <= capacity as usize)
+    }
+
+    /// Whether (x, y) is a shelter cell that can no longer accept agents
+    pub fn is_full_shelter(&self, x: u32, y: u32) -> bool {
+        match self.terrain[y as usize][x as usize] {
+            Terrain::Shelter(shelter_id) => !self.shelter_has_space(shelter_id),
+            _ => false,
+        }
+    }
+
+    /// Source of tsunami frames: the attached source if any, else `tsunami_data`
+    pub fn tsunami(&self) -> &dyn TsunamiSource {
+        match &self.tsunami_source.0 {
+            Some(source) => source.as_ref(),
+            None => &self.tsunami_data,
+        }
+    }
+
+    /// Replace the in-memory frames with another source
+    pub fn set_tsunami_source(&mut self, source: Arc<dyn TsunamiSource>) {
+        self.tsunami_data = Vec::new();
+        self.tsunami_source = TsunamiSourceHandle(Some(source));
+    }
+
+    /// Number of tsunami frames available
+    pub fn tsunami_frame_count(&self) -> usize {
+        self.tsunami().frame_count()
+    }
+
+    /// Decoded tsunami frame, or None if out of range or unreadable
+    pub fn tsunami_frame(&self, frame: usize) -> Option<FrameRef<'_>> {
+        if frame >= self.tsunami_frame_count() {
+            return None;
+        }
+        match self.tsunami().frame(frame) {
+            Ok(frame) => Some(frame),
+            Err(e) => {
+                eprintln!("Error reading tsunami frame {}: {}", frame, e);
+                None
+            }
+        }
+    }
+
+    /// Inundation depth in metres at (x, y) in a tsunami frame (0 outside the data)
+    pub fn tsunami_depth(&self, frame: usize, x: u32, y: u32) -> f32 {
+        self.tsunami_frame(frame).map_or(0.0, |frame| frame.depth(x, y))
+    }
+
+    /// Inundation depth in metres at (x, y) at a fractional frame position,
+    /// e.g. 2.25 is a quarter of the way from frame 2 to frame 3. Positions past
+    /// the last frame hold its depth. `MaxSoFar` keeps the peak recorded in
+    /// `envelope`, which must have been advanced through the frame at or
+    /// before the position; without one it samples like `Linear`. Use
+    /// `DepthSampler` for many lookups.
+    pub fn tsunami_depth_at(
+        &self,
+        position: f64,
+        x: u32,
+        y: u32,
+        mode: FrameInterpolation,
+        envelope: Option<&MaxDepthEnvelope>,
+    ) -> f32 {
+        DepthSampler::new(self.tsunami(), position, mode, envelope).depth(x, y)
     }
 
     pub fn add_agent(&mut self, x: u32, y: u32, agent_id: usize) {
         self.agents_in_cell[y as usize][x as usize].push(agent_id);
     }
 
+    /// Agents that fit in cell (x, y) given the configured density of its
+    /// terrain, at least one; None when no terrain densities are configured
+    pub fn cell_capacity(&self, x: u32, y: u32) -> Option<usize> {
+        let densities = self.config.cell_densities.as_ref()?;
+        let density = densities.for_terrain(self.terrain[y as usize][x as usize]);
+        Some(((density * self.cellsize * self.cellsize).floor() as usize).max(1))
+    }
+
+    /// Agents per square metre in the square of cells within `radius` of
+    /// (x, y), counting only the part of the square inside the grid
+    pub fn local_density(&self, x: u32, y: u32, radius: u32) -> f64 {
+        let x_range = x.saturating_sub(radius)..=(x + radius).min(self.width - 1);
+        let y_range = y.saturating_sub(radius)..=(y + radius).min(self.height - 1);
+        let cells = (x_range.clone().count() * y_range.clone().count()) as f64;
+        let agents: usize = y_range
+            .flat_map(|cy| x_range.clone().map(move |cx| (cx, cy)))
+            .map(|(cx, cy)| self.agents_in_cell[cy as usize][cx as usize].len())
+            .sum();
+        agents as f64 / (cells * self.cellsize * self.cellsize)
+    }
+
+    /// Whether (x, y) was under water in the latest frame applied to routing
+    pub fn is_flooded(&self, x: u32, y: u32) -> bool {
+        self.flooded
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Whether routes must not pass through (x, y) because it is flooded
+    fn blocks_routes(&self, x: u32, y: u32) -> bool {
+        matches!(self.config.flood_routing, FloodRouting::Impassable { .. }) && self.is_flooded(x, y)
+    }
+
+    /// Extra routing cost of leaving (x, y) because it is flooded
+    fn flood_cost(&self, x: u32, y: u32) -> u32 {
+        if !self.is_flooded(x, y) {
+            return 0;
+        }
+        match self.config.flood_routing {
+            FloodRouting::Ignore => 0,
+            FloodRouting::Impassable { .. } => IMPASSABLE_FLOOD_COST,
+            FloodRouting::Penalty { cost, .. } => cost,
+        }
+    }
+
+    /// Whether any flooded cell adds to the cost of routes through it
+    fn has_flood_costs(&self) -> bool {
+        (0..self.height).any(|y| (0..self.width).any(|x| self.flood_cost(x, y) > 0))
+    }
+
+    /// Mark the cells flooded in a tsunami frame and, if that changes which
+    /// cells are under water, recompute the shelter distance fields so agents
+    /// divert around the water. Shelter cells are never treated as flooded.
+    /// Returns whether the routes changed.
+    pub fn update_flooding(&mut self, frame: usize) -> bool {
+        if !self.config.flood_routing.is_enabled() {
+            return false;
+        }
+
+        let Some(depths) = self.tsunami_frame(frame) else {
+            return false;
+        };
+        let flooded: Vec<Vec<bool>> = (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        !matches!(self.terrain[y as usize][x as usize], Terrain::Shelter(_))
+                            && self.config.flood_routing.is_flooded(depths.depth(x, y) as f64)
+                    })
+                    .collect()
+            })
+            .collect();
+        drop(depths);
+
+        let unchanged = if self.flooded.is_empty() {
+            flooded.iter().flatten().all(|&wet| !wet)
+        } else {
+            self.flooded == flooded
+        };
+        self.flooded = flooded;
+        if unchanged {
+            return false;
+        }
+
+        self.compute_distance_to_shelters();
+        if !self.shelter_distance_fields.is_empty() {
+            self.compute_shelter_distance_fields();
+        }
+        true
+    }
+
     /// Compute the distance from each cell to the nearest shelter
 pub fn compute_distance_to_shelters(&mut self) {
         // Choose the appropriate algorithm based on configuration
+        // With A* agents follow individual routes (see `Model::plan_agent_paths`);
+        // the shared distance field they fall back on is built with Dijkstra.
+        // Breadth-first search only finds shortest routes while every step
+        // costs the same, so flood costs also call for Dijkstra.
         match self.config.path_algorithm.as_str() {
-            "bfs" => self.compute_distance_to_shelters_bfs(),
-            "a_star" => self.compute_distance_to_shelters_astar(),
+            "bfs" if !self.has_flood_costs() => self.compute_distance_to_shelters_bfs(),
             _ => self.compute_distance_to_shelters_dijkstra(), // Default to Dijkstra
         }
     }
@@ -127,8 +623,10 @@ pub fn compute_distance_to_shelters(&mut self) {
         let mut queue = VecDeque::new();
         let mut visited = vec![vec![false; self.width as usize]; self.height as usize];
 
-        // Initialize all shelters with distance 0
-        for &(x, y, _) in &self.shelters {
+        self.distance_to_shelter = vec![vec![None; self.width as usize]; self.height as usize];
+
+        // Initialize the entrances of all shelters with remaining space with distance 0
+        for (x, y) in self.open_shelter_entrances() {
             let x = x as usize;
             let y = y as usize;
             self.distance_to_shelter[y][x] = Some(0);
@@ -150,6 +648,10 @@ pub fn compute_distance_to_shelters(&mut self) {
 
         while let Some((x, y)) = queue.pop_front() {
             let current_dist = self.distance_to_shelter[y][x].unwrap();
+            // Flooded cells are a way out for agents standing in them, never a corridor
+            if self.blocks_routes(x as u32, y as u32) {
+                continue;
+            }
 
             for &(dx, dy) in &dirs {
                 let nx = (x as i32) + dx;
@@ -159,8 +661,13 @@ pub fn compute_distance_to_shelters(&mut self) {
                     let nx = nx as usize;
                     let ny = ny as usize;
 
-                    // Don't visit blocked cells or already visited cells
-                    if !visited[ny][nx] && self.terrain[ny][nx] != Terrain::Blocked {
+                    // Don't visit blocked cells, full shelters, shelter
+                    // interiors or already visited cells
+                    if !visited[ny][nx]
+                        && self.terrain[ny][nx] != Terrain::Blocked
+                        && !self.is_full_shelter(nx as u32, ny as u32)
+                        && !self.is_shelter_interior(nx as u32, ny as u32)
+                    {
                         visited[ny][nx] = true;
                         
                         // Calculate step cost (1 for cardinal, √2 for diagonal)
@@ -175,32 +682,127 @@ pub fn compute_distance_to_shelters(&mut self) {
     
     /// Compute shelter distances using Dijkstra's algorithm (accounts for terrain costs)
     fn compute_distance_to_shelters_dijkstra(&mut self) {
+        // Initialize the entrances of all shelters with remaining space with distance 0
+        let sources = self.open_shelter_entrances();
+
+        // Full shelters turn agents away and shelter interiors are only
+        // reached through an entrance, so routes cannot pass through either
+        self.distance_to_shelter = self.shelter_distance_dijkstra(&sources, |x, y| {
+            !self.is_full_shelter(x, y) && !self.is_shelter_interior(x, y)
+        });
+    }
+
+    /// Compute one distance field per shelter ID, used by shelter-choice policies
+    /// that send agents to a particular shelter rather than the nearest one.
+    /// Cells of other shelters are destinations, not corridors, so each field
+    /// routes around them.
+    pub fn compute_shelter_distance_fields(&mut self) {
+        let fields: HashMap<u32, Vec<Vec<Option<u32>>>> = self
+            .shelter_ids()
+            .into_par_iter()
+            .map(|shelter_id| {
+                let sources = self.shelter_entrance_cells(shelter_id);
+                let field = self.shelter_distance_dijkstra(&sources, |x, y| {
+                    !matches!(
+                        self.terrain[y as usize][x as usize],
+                        Terrain::Shelter(id) if id != shelter_id
+                    ) && !self.is_shelter_interior(x, y)
+                });
+                (shelter_id, field)
+            })
+            .collect();
+
+        self.shelter_distance_fields = fields;
+    }
+
+    /// Heading of the sign at (x, y), if there is one
+    pub fn sign_at(&self, x: u32, y: u32) -> Option<SignHeading> {
+        self.signs
+            .iter()
+            .find(|&&(sx, sy, _)| (sx, sy) == (x, y))
+            .map(|&(_, _, heading)| heading)
+    }
+
+    /// Real-world coordinates of cell (x, y), as used in the output files
+    pub fn cell_coordinates(&self, x: u32, y: u32) -> (f64, f64) {
+        (
+            self.xllcorner + x as f64 * self.cellsize,
+            self.yllcorner + (self.nrow as f64 - y as f64) * self.cellsize,
+        )
+    }
+
+    /// Cells agents can enter a shelter through: its designated entrances, or
+    /// every cell of its footprint if it has none
+    pub fn shelter_entrance_cells(&self, shelter_id: u32) -> Vec<(u32, u32)> {
+        let entrances: Vec<(u32, u32)> = self
+            .shelter_entrances
+            .iter()
+            .filter(|&&(_, _, id)| id == shelter_id)
+            .map(|&(x, y, _)| (x, y))
+            .collect();
+        if !entrances.is_empty() {
+            return entrances;
+        }
+        self.shelters
+            .iter()
+            .filter(|&&(_, _, id)| id == shelter_id)
+            .map(|&(x, y, _)| (x, y))
+            .collect()
+    }
+
+    /// Whether (x, y) is a shelter cell agents can enter the shelter through
+    pub fn is_shelter_entrance(&self, x: u32, y: u32) -> bool {
+        let Terrain::Shelter(shelter_id) = self.terrain[y as usize][x as usize] else {
+            return false;
+        };
+        self.shelter_entrances.contains(x, y, shelter_id) || !self.shelter_entrances.has_entrances(shelter_id)
+    }
+
+    /// Whether (x, y) belongs to a shelter's footprint but is not one of its
+    /// entrances; such cells are neither entered nor crossed
+    pub fn is_shelter_interior(&self, x: u32, y: u32) -> bool {
+        matches!(self.terrain[y as usize][x as usize], Terrain::Shelter(_)) && !self.is_shelter_entrance(x, y)
+    }
+
+    /// Entrance cells of every shelter that can still accept agents
+    fn open_shelter_entrances(&self) -> Vec<(u32, u32)> {
+        self.shelter_ids()
+            .into_iter()
+            .filter(|&shelter_id| self.shelter_has_space(shelter_id))
+            .flat_map(|shelter_id| self.shelter_entrance_cells(shelter_id))
+            .collect()
+    }
+
+    /// Distinct shelter IDs in ascending order
+    pub fn shelter_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.shelters.iter().map(|&(_, _, id)| id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Distance from (x, y) to a specific shelter, if its field has been computed
+    /// and the shelter is reachable
+    pub fn distance_to_shelter_id(&self, shelter_id: u32, x: u32, y: u32) -> Option<u32> {
+        self.shelter_distance_fields
+            .get(&shelter_id)
+            .and_then(|field| field[y as usize][x as usize])
+    }
+
+    /// Multi-source Dijkstra from `sources` over cells accepted by `passable`
+    fn shelter_distance_dijkstra<F>(&self, sources: &[(u32, u32)], passable: F) -> Vec<Vec<Option<u32>>>
+    where
+        F: Fn(u32, u32) -> bool,
+    {
         let mut dist = vec![vec![None; self.width as usize]; self.height as usize];
         let mut heap = BinaryHeap::new();
 
-        // Initialize all shelters with distance 0
-        for &(x, y, _) in &self.shelters {
-            let x = x as usize;
-            let y = y as usize;
-            dist[y][x] = Some(0);
-            heap.push(State {
-                cost: 0,
-                x: x as u32,
-                y: y as u32,
-            });
+        for &(x, y) in sources {
+            dist[y as usize][x as usize] = Some(0);
+            heap.push(State { cost: 0, x, y });
         }
 
-        // Define movement directions (4-connected or 8-connected grid)
-        let dirs = if self.config.allow_diagonal {
-            // 8-connected grid (includes diagonals)
-            vec![
-                (0, 1), (0, -1), (1, 0), (-1, 0),  // Cardinal directions
-                (1, 1), (1, -1), (-1, 1), (-1, -1)  // Diagonals
-            ]
-        } else {
-            // 4-connected grid (cardinal directions only)
-            vec![(0, 1), (0, -1), (1, 0), (-1, 0)]
-        };
+        let dirs = self.movement_directions();
 
         // Use Dijkstra's algorithm to compute shortest paths
         while let Some(State { cost, x, y }) = heap.pop() {
@@ -210,6 +812,9 @@ pub fn compute_distance_to_shelters(&mut self) {
                     continue;
                 }
             }
+            if self.blocks_routes(x, y) {
+                continue;
+            }
             
             for &(dx, dy) in &dirs {
                 let nx = x as i32 + dx;
@@ -218,23 +823,12 @@ pub fn compute_distance_to_shelters(&mut self) {
                 if nx >= 0 && ny >= 0 && nx < self.width as i32 && ny < self.height as i32 {
                     let nx = nx as u32;
                     let ny = ny as u32;
+
+                    if !passable(nx, ny) {
+                        continue;
+                    }
                     
-                    // Determine cost based on terrain type
-                    let extra_cost = match self.terrain[ny as usize][nx as usize] {
-                        Terrain::Blocked => self.config.blocked_penalty,
-                        Terrain::Road => 1,
-                        Terrain::Shelter(_) => 0, // No cost for shelters
-                        Terrain::Custom(cost) => cost.ceil() as u32,
-                    };
-                    
-                    // Apply diagonal penalty if movement is diagonal
-                    let diagonal_cost = if dx != 0 && dy != 0 { 
-                        (1.414 * extra_cost as f64).ceil() as u32 // √2 for diagonal movement
-                    } else {
-                        extra_cost
-                    };
-                    
-                    let next_cost = cost + diagonal_cost;
+                    let next_cost = cost + self.shelter_step_cost(nx, ny, dx, dy);
                     
                     // Update distance if cell not visited or shorter path found
                     if dist[ny as usize][nx as usize].is_none()
@@ -250,20 +844,233 @@ pub fn compute_distance_to_shelters(&mut self) {
                 }
             }
         }
-        
-        // Store computed distances
-        self.distance_to_shelter = dist;
+
+        dist
     }
     
-    /// Compute shelter distances using A* algorithm 
-    /// (placeholder - would require a heuristic function)
-    fn compute_distance_to_shelters_astar(&mut self) {
-        // For now, just use Dijkstra - A* implementation would be similar
-        // but with an added heuristic function to improve performance
-        self.compute_distance_to_shelters_dijkstra();
+    /// Terrain cost charged for cell (x, y) when it lies on a shelter route,
+    /// in the same units as `distance_to_shelter`
+    fn shelter_step_cost(&self, x: u32, y: u32, dx: i32, dy: i32) -> u32 {
+        // Determine cost based on terrain type
+        let extra_cost = match self.terrain[y as usize][x as usize] {
+            Terrain::Blocked => self.config.blocked_penalty,
+            Terrain::Road => 1,
+            Terrain::Shelter(_) => 0, // No cost for shelters
+            Terrain::Custom(cost) => cost.ceil() as u32,
+        } + self.flood_cost(x, y);
+
+        // Apply diagonal penalty if movement is diagonal
+        if dx != 0 && dy != 0 {
+            (1.414 * extra_cost as f64).ceil() as u32 // √2 for diagonal movement
+        } else {
+            extra_cost
+        }
+    }
+
+    /// Plan a route from (start_x, start_y) to the nearest reachable shelter with
+    /// remaining capacity using A*.
+    ///
+    /// The distance field is grown outwards from the shelters, so each move is
+    /// charged the terrain cost of the cell being left. With the same costs, the
+    /// `cost` of the returned plan equals `distance_to_shelter` at the start cell.
+    /// The heuristic is the octile (or Manhattan, without diagonals) distance to
+    /// the closest shelter scaled by the cheapest non-shelter step in the grid,
+    /// which never overestimates the remaining cost.
+    pub fn plan_path_astar(&self, start_x: u32, start_y: u32) -> Option<PathPlan> {
+        if start_x >= self.width || start_y >= self.height {
+            return None;
+        }
+
+        // Only the entrances of shelters that can still accept agents are goals
+        let open_shelters = self.open_shelter_entrances();
+        if open_shelters.is_empty() {
+            return None;
+        }
+
+        let dirs = self.movement_directions();
+        let min_step = self.min_step_cost();
+        let min_diagonal_step = (1.414 * min_step as f64).ceil() as u32;
+        let heuristic = |x: u32, y: u32| -> u32 {
+            open_shelters
+                .iter()
+                .map(|&(sx, sy)| {
+                    let dx = x.abs_diff(sx);
+                    let dy = y.abs_diff(sy);
+                    if self.config.allow_diagonal {
+                        let diagonal = dx.min(dy);
+                        let straight = dx.max(dy) - diagonal;
+                        diagonal * min_diagonal_step + straight * min_step
+                    } else {
+                        (dx + dy) * min_step
+                    }
+                })
+                .min()
+                .unwrap_or(0)
+        };
+
+        let width = self.width as usize;
+        let mut g_score: Vec<Option<u32>> = vec![None; width * self.height as usize];
+        let mut came_from: Vec<Option<(u32, u32)>> = vec![None; width * self.height as usize];
+        let mut heap = BinaryHeap::new();
+        let mut nodes_expanded = 0;
+
+        g_score[start_y as usize * width + start_x as usize] = Some(0);
+        heap.push(State {
+            cost: heuristic(start_x, start_y),
+            x: start_x,
+            y: start_y,
+        });
+
+        while let Some(State { cost, x, y }) = heap.pop() {
+            let g = g_score[y as usize * width + x as usize].unwrap();
+            // Skip stale heap entries
+            if cost > g + heuristic(x, y) {
+                continue;
+            }
+            nodes_expanded += 1;
+
+            if let Terrain::Shelter(shelter_id) = self.terrain[y as usize][x as usize] {
+                if self.shelter_has_space(shelter_id) && self.is_shelter_entrance(x, y) {
+                    let mut path = vec![(x, y)];
+                    let mut current = (x, y);
+                    while let Some(prev) = came_from[current.1 as usize * width + current.0 as usize] {
+                        path.push(prev);
+                        current = prev;
+                    }
+                    path.reverse();
+
+                    let length_m = path
+                        .windows(2)
+                        .map(|w| {
+                            if w[0].0 != w[1].0 && w[0].1 != w[1].1 {
+                                std::f64::consts::SQRT_2 * self.cellsize
+                            } else {
+                                self.cellsize
+                            }
+                        })
+                        .sum();
+
+                    return Some(PathPlan {
+                        path,
+                        cost: g,
+                        length_m,
+                        shelter_id,
+                        nodes_expanded,
+                    });
+                }
+            }
+
+            if self.blocks_routes(x, y) && (x, y) != (start_x, start_y) {
+                continue;
+            }
+
+            for &(dx, dy) in &dirs {
+                let nx = x as i32 + dx;
+                let ny = y as i32 + dy;
+
+                if nx >= 0 && ny >= 0 && nx < self.width as i32 && ny < self.height as i32 {
+                    let nx = nx as u32;
+                    let ny = ny as u32;
+                    if self.is_full_shelter(nx, ny) || self.is_shelter_interior(nx, ny) {
+                        continue;
+                    }
+                    let index = ny as usize * width + nx as usize;
+                    let next_g = g + self.shelter_step_cost(x, y, dx, dy);
+
+                    if g_score[index].is_none() || next_g < g_score[index].unwrap() {
+                        g_score[index] = Some(next_g);
+                        came_from[index] = Some((x, y));
+                        heap.push(State {
+                            cost: next_g + heuristic(nx, ny),
+                            x: nx,
+                            y: ny,
+                        });
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Follow the precomputed `distance_to_shelter` field downhill from a cell,
+    /// giving the route an agent using the global field would take
+    pub fn trace_distance_field_path(&self, start_x: u32, start_y: u32) -> Option<Vec<(u32, u32)>> {
+        if start_x >= self.width || start_y >= self.height {
+            return None;
+        }
+        self.distance_to_shelter[start_y as usize][start_x as usize]?;
+
+        let dirs = self.movement_directions();
+        let mut path = vec![(start_x, start_y)];
+        let (mut x, mut y) = (start_x, start_y);
+
+        while !matches!(
+            self.terrain[y as usize][x as usize],
+            Terrain::Shelter(shelter_id) if self.shelter_has_space(shelter_id) && self.is_shelter_entrance(x, y)
+        ) {
+            let current = self.distance_to_shelter[y as usize][x as usize]?;
+            // Step to the neighbour whose distance accounts for the current one
+            let next = dirs.iter().find_map(|&(dx, dy)| {
+                let nx = x as i32 + dx;
+                let ny = y as i32 + dy;
+                if nx < 0 || ny < 0 || nx >= self.width as i32 || ny >= self.height as i32 {
+                    return None;
+                }
+                let (nx, ny) = (nx as u32, ny as u32);
+                let dist = self.distance_to_shelter[ny as usize][nx as usize]?;
+                // Distances are computed from the shelter outwards, so the cost
+                // paid to enter (x, y) from its neighbour is what links them
+                if dist + self.shelter_step_cost(x, y, dx, dy) == current {
+                    Some((nx, ny))
+                } else {
+                    None
+                }
+            })?;
+            path.push(next);
+            x = next.0;
+            y = next.1;
+
+            // Zero-cost terrain can create plateaus; never walk more cells than exist
+            if path.len() > (self.width * self.height) as usize {
+                return None;
+            }
+        }
+
+        Some(path)
+    }
+
+    /// Neighbour offsets for the configured connectivity
+    fn movement_directions(&self) -> Vec<(i32, i32)> {
+        if self.config.allow_diagonal {
+            // 8-connected grid (includes diagonals)
+            vec![
+                (0, 1), (0, -1), (1, 0), (-1, 0),  // Cardinal directions
+                (1, 1), (1, -1), (-1, 1), (-1, -1)  // Diagonals
+            ]
+        } else {
+            // 4-connected grid (cardinal directions only)
+            vec![(0, 1), (0, -1), (1, 0), (-1, 0)]
+        }
+    }
+
+    /// Cheapest cardinal step into any non-shelter cell, used to scale the A* heuristic
+    fn min_step_cost(&self) -> u32 {
+        self.terrain
+            .iter()
+            .flatten()
+            .filter_map(|terrain| match *terrain {
+                Terrain::Blocked => Some(self.config.blocked_penalty),
+                Terrain::Road => Some(1),
+                Terrain::Shelter(_) => None,
+                Terrain::Custom(cost) => Some(cost.ceil() as u32),
+            })
+            .min()
+            .unwrap_or(0)
     }
 
     /// Compute the distance from each cell to the nearest road
+#[allow(clippy::needless_range_loop)]
 pub fn compute_road_distances_from_agents(&mut self) {
         // Initialize distance matrix with None
         let mut dist = vec![vec![None; self.width as usize]; self.height as usize];
@@ -361,6 +1168,13 @@ pub fn load_grid_from_ascii_with_config(
     path: &str,
     config: GridConfig,
 ) -> Result<(Grid, Vec<crate::game::agent::Agent>), std::io::Error> {
+    if !matches!(config.path_algorithm.as_str(), "dijkstra" | "bfs" | "a_star") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Unknown path algorithm: {}", config.path_algorithm),
+        ));
+    }
+
     println!("Opening file {}", path);
     let content = std::fs::read_to_string(path)?;
     let mut lines = content.lines();
@@ -368,54 +1182,54 @@ pub fn load_grid_from_ascii_with_config(
     // Parse header (6 lines)
     let ncols_line = lines
         .next()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing ncols line"))?;
+        .ok_or_else(|| std::io::Error::other("Missing ncols line"))?;
     let nrows_line = lines
         .next()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing nrows line"))?;
+        .ok_or_else(|| std::io::Error::other("Missing nrows line"))?;
     let xll_line = lines
         .next()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing xllcorner line"))?;
+        .ok_or_else(|| std::io::Error::other("Missing xllcorner line"))?;
     let yll_line = lines
         .next()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing yllcorner line"))?;
+        .ok_or_else(|| std::io::Error::other("Missing yllcorner line"))?;
     let cellsize_line = lines
         .next()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing cellsize line"))?;
+        .ok_or_else(|| std::io::Error::other("Missing cellsize line"))?;
     let _nodata_line = lines.next().ok_or_else(|| {
-        std::io::Error::new(std::io::ErrorKind::Other, "Missing NODATA_value line")
+        std::io::Error::other("Missing NODATA_value line")
     })?;
 
     // Parse header values
     let ncols: u32 = ncols_line
         .split_whitespace()
         .nth(1)
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse ncols"))?
+        .ok_or_else(|| std::io::Error::other("Cannot parse ncols"))?
         .parse()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Invalid ncols value"))?;
+        .map_err(|_| std::io::Error::other("Invalid ncols value"))?;
     let nrows: u32 = nrows_line
         .split_whitespace()
         .nth(1)
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse nrows"))?
+        .ok_or_else(|| std::io::Error::other("Cannot parse nrows"))?
         .parse()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Invalid nrows value"))?;
+        .map_err(|_| std::io::Error::other("Invalid nrows value"))?;
     let xllcorner: f64 = xll_line
         .split_whitespace()
         .nth(1)
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse xllcorner"))?
+        .ok_or_else(|| std::io::Error::other("Cannot parse xllcorner"))?
         .parse()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Invalid xllcorner value"))?;
+        .map_err(|_| std::io::Error::other("Invalid xllcorner value"))?;
     let yllcorner: f64 = yll_line
         .split_whitespace()
         .nth(1)
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse yllcorner"))?
+        .ok_or_else(|| std::io::Error::other("Cannot parse yllcorner"))?
         .parse()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Invalid yllcorner value"))?;
+        .map_err(|_| std::io::Error::other("Invalid yllcorner value"))?;
     let cellsize: f64 = cellsize_line
         .split_whitespace()
         .nth(1)
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse cellsize"))?
+        .ok_or_else(|| std::io::Error::other("Cannot parse cellsize"))?
         .parse()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Invalid cellsize value"))?;
+        .map_err(|_| std::io::Error::other("Invalid cellsize value"))?;
 
     println!("Cellsize: {}", cellsize);
     println!("Nrows: {}", nrows);
@@ -428,6 +1242,8 @@ pub fn load_grid_from_ascii_with_config(
     // Initialize grid data structures
     let mut terrain = vec![vec![Terrain::Blocked; ncols as usize]; nrows as usize];
     let mut shelters = Vec::new();
+    let mut signs = Vec::new();
+    let mut shelter_entrances = Vec::new();
     let mut agent_positions = Vec::new();
     let mut road_cells = Vec::new();
 
@@ -444,13 +1260,24 @@ pub fn load_grid_from_ascii_with_config(
                     road_cells.push((x as u32, y as u32));
                     Terrain::Road
                 }
+                // Entrance cell of a shelter, e.g. e2001
+                token if token.starts_with("e20") => {
+                    if let Ok(shelter_id) = token[3..].parse::<u32>() {
+                        shelters.push((x as u32, y as u32, shelter_id));
+                        shelter_entrances.push((x as u32, y as u32, shelter_id));
+                        Terrain::Shelter(shelter_id)
+                    } else {
+                        return Err(std::io::Error::other(
+                            format!("Invalid shelter ID format: {}", token),
+                        ));
+                    }
+                }
                 token if token.starts_with("20") => {
                     if let Ok(shelter_id) = token[2..].parse::<u32>() {
                         shelters.push((x as u32, y as u32, shelter_id));
                         Terrain::Shelter(shelter_id)
                     } else {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
+                        return Err(std::io::Error::other(
                             format!("Invalid shelter ID format: {}", token),
                         ));
                     }
@@ -474,6 +1301,18 @@ pub fn load_grid_from_ascii_with_config(
                     agent_positions.push((x as u32, y as u32, AgentType::Elder));
                     Terrain::Road
                 }
+                // Evacuation sign on a road, e.g. sN or sSE
+                token if token.starts_with('s') => {
+                    if let Some(heading) = SignHeading::parse(&token[1..]) {
+                        signs.push((x as u32, y as u32, heading));
+                        road_cells.push((x as u32, y as u32));
+                        Terrain::Road
+                    } else {
+                        return Err(std::io::Error::other(
+                            format!("Invalid sign heading: {}", token),
+                        ));
+                    }
+                }
                 // Support for custom terrain with costs
                 token if token.starts_with("c") => {
                     if let Ok(cost) = token[1..].parse::<f64>() {
@@ -509,12 +1348,18 @@ pub fn load_grid_from_ascii_with_config(
         cellsize,
         terrain,
         shelters,
+        shelter_entrances: shelter_entrances.into(),
+        signs,
         agents_in_cell: vec![vec![Vec::new(); ncols as usize]; nrows as usize],
         distance_to_shelter: vec![vec![None; ncols as usize]; nrows as usize],
+        shelter_distance_fields: HashMap::new(),
+        flooded: Vec::new(),
+        failed_shelters: HashSet::new(),
         shelter_agents: std::collections::HashMap::new(),
         distance_to_road: vec![vec![None; ncols as usize]; nrows as usize],
         population: vec![vec![0; ncols as usize]; nrows as usize],
         tsunami_data: Vec::new(),
+        tsunami_source: TsunamiSourceHandle::default(),
         nrow: nrows,
         ncol: ncols,
         config,
@@ -0,0 +1,129 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use super::agent::Agent;
+
+/// How agents are grouped into households that evacuate together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HouseholdConfig {
+    /// Relative weights of household sizes 1, 2, 3, ... drawn while splitting
+    /// the agents of every cell (default: [1.0], everyone on their own)
+    pub size_weights: Vec<f64>,
+    /// Seconds a household needs to gather once its last member is ready
+    /// to leave (default: 0)
+    pub gathering_time: f64,
+}
+
+impl Default for HouseholdConfig {
+    fn default() -> Self {
+        HouseholdConfig {
+            size_weights: vec![1.0],
+            gathering_time: 0.0,
+        }
+    }
+}
+
+impl HouseholdConfig {
+    /// Draw a household size, at least one
+    pub fn sample_size<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        if self.size_weights.len() <= 1 {
+            return 1;
+        }
+        match WeightedIndex::new(&self.size_weights) {
+            Ok(dist) => dist.sample(rng) + 1,
+            Err(_) => 1,
+        }
+    }
+}
+
+/// Agents that evacuate together, starting from the same cell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Household {
+    pub id: usize,
+    /// Ids of the member agents
+    pub members: Vec<usize>,
+    /// Cell the household gathers in
+    pub home: (u32, u32),
+}
+
+/// What became of a household's members
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HouseholdOutcome {
+    pub id: usize,
+    pub size: usize,
+    pub sheltered: usize,
+    pub dead: usize,
+    /// Members neither sheltered nor dead
+    pub evacuating: usize,
+}
+
+impl HouseholdOutcome {
+    /// Tally the members of a household given the ids of the living and the sheltered agents
+    pub fn tally(household: &Household, living: &HashSet<usize>, sheltered: &HashSet<usize>) -> Self {
+        let mut outcome = HouseholdOutcome {
+            id: household.id,
+            size: household.members.len(),
+            sheltered: 0,
+            dead: 0,
+            evacuating: 0,
+        };
+        for member in &household.members {
+            if !living.contains(member) {
+                outcome.dead += 1;
+            } else if sheltered.contains(member) {
+                outcome.sheltered += 1;
+            } else {
+                outcome.evacuating += 1;
+            }
+        }
+        outcome
+    }
+}
+
+/// Split the agents from `first` on into households of the agents sharing a
+/// cell, with sizes drawn from `config`. Agents left on their own form no
+/// household. Members walk at the pace of the slowest of them.
+pub fn form_households<R: Rng + ?Sized>(
+    agents: &mut [Agent],
+    first: usize,
+    config: &HouseholdConfig,
+    rng: &mut R,
+) -> Vec<Household> {
+    let mut cells: BTreeMap<(u32, u32), Vec<usize>> = BTreeMap::new();
+    for (index, agent) in agents.iter().enumerate().skip(first) {
+        cells.entry((agent.y, agent.x)).or_default().push(index);
+    }
+
+    let mut households = Vec::new();
+    for ((y, x), residents) in cells {
+        let mut remaining = residents.as_slice();
+        while !remaining.is_empty() {
+            let size = config.sample_size(rng).min(remaining.len());
+            let (members, rest) = remaining.split_at(size);
+            remaining = rest;
+            if size == 1 {
+                continue;
+            }
+
+            let pace = members
+                .iter()
+                .map(|&index| agents[index].speed)
+                .fold(f64::INFINITY, f64::min);
+            let household_id = households.len();
+            for &index in members {
+                agents[index].speed = pace;
+                agents[index].household = Some(household_id);
+            }
+
+            households.push(Household {
+                id: household_id,
+                members: members.iter().map(|&index| agents[index].id).collect(),
+                home: (x, y),
+            });
+        }
+    }
+
+    households
+}
@@ -0,0 +1,57 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::agent::AgentType;
+
+/// What an agent knows about the way to safety
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum RouteKnowledge {
+    /// Knows the way to the nearest shelter and follows the distance field
+    #[default]
+    Informed,
+    /// Knows only the roads around it: heads for a shelter in sight, else
+    /// follows the heading of informed neighbours, then of the crowd, and
+    /// otherwise keeps walking the way it was going
+    Local,
+}
+
+/// Route knowledge of the population and how far uninformed agents perceive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnowledgeConfig {
+    /// Fraction of agents of each type that know the route
+    /// [Child, Teen, Adult, Elder] (default: everyone)
+    pub informed_share: [f64; 4],
+    /// Cells on each side of an uninformed agent it can see shelters and
+    /// other agents in (default: 3)
+    pub perception_radius: u32,
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        KnowledgeConfig {
+            informed_share: [1.0; 4],
+            perception_radius: 3,
+        }
+    }
+}
+
+impl KnowledgeConfig {
+    /// Fraction of agents of a type that know the route
+    pub fn informed_share(&self, agent_type: AgentType) -> f64 {
+        self.informed_share[agent_type.profile_index()]
+    }
+
+    /// Whether every agent knows the route, so no draws are needed
+    pub fn everyone_informed(&self) -> bool {
+        self.informed_share.iter().all(|&share| share >= 1.0)
+    }
+
+    /// Draw the route knowledge of an agent of this type
+    pub fn sample<R: Rng + ?Sized>(&self, agent_type: AgentType, rng: &mut R) -> RouteKnowledge {
+        if rng.gen::<f64>() < self.informed_share(agent_type) {
+            RouteKnowledge::Informed
+        } else {
+            RouteKnowledge::Local
+        }
+    }
+}
@@ -24,4 +24,14 @@ impl PartialOrd for State {
 
 pub mod grid;
 pub mod agent;
+pub mod clock;
+pub mod density;
+pub mod departure;
+pub mod fragility;
+pub mod household;
+pub mod knowledge;
+pub mod rng;
+pub mod shelter;
+pub mod vehicle;
+#[allow(clippy::module_inception)]
 pub mod game;
\ No newline at end of file
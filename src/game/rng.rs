@@ -0,0 +1,82 @@
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Random number generator used throughout the simulation. ChaCha output is
+/// fixed for a given seed and stream, across platforms and crate versions.
+pub type SimRng = ChaCha8Rng;
+
+/// Independent random streams derived from one seed, one per subsystem, so a
+/// change in how often one subsystem draws does not shift the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    /// Agent types drawn when populating the grid
+    Population = 0,
+    /// Order in which agents move each step
+    AgentOrder = 1,
+    /// Choice among fallback moves when no cell is closer to a shelter
+    FallbackMoves = 2,
+    /// Shelter-choice policies
+    ShelterChoice = 3,
+    /// Per-agent vulnerability in the hazard model
+    Hazard = 4,
+    /// Sample points of parameter sweeps
+    Sweep = 5,
+    /// Departure (milling) times
+    Departure = 6,
+    /// Whether agents walk or drive
+    TravelMode = 7,
+    /// Household sizes and the types of added members
+    Households = 8,
+    /// Which agents know the route to a shelter
+    Knowledge = 9,
+}
+
+/// Create the generator for one subsystem stream of a seed
+pub fn stream_rng(seed: u64, stream: RngStream) -> SimRng {
+    let mut rng = SimRng::seed_from_u64(seed);
+    rng.set_stream(stream as u64);
+    rng
+}
+
+/// Draw a fresh seed from the operating system, for runs without a fixed seed
+pub fn random_seed() -> u64 {
+    rand::rngs::OsRng.next_u64()
+}
+
+/// The per-subsystem generators owned by a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct RngStreams {
+    seed: u64,
+    pub agent_order: SimRng,
+    pub fallback_moves: SimRng,
+    pub shelter_choice: SimRng,
+    pub hazard: SimRng,
+    pub departure: SimRng,
+    pub travel_mode: SimRng,
+    pub knowledge: SimRng,
+}
+
+impl RngStreams {
+    pub fn new(seed: u64) -> Self {
+        RngStreams {
+            seed,
+            agent_order: stream_rng(seed, RngStream::AgentOrder),
+            fallback_moves: stream_rng(seed, RngStream::FallbackMoves),
+            shelter_choice: stream_rng(seed, RngStream::ShelterChoice),
+            hazard: stream_rng(seed, RngStream::Hazard),
+            departure: stream_rng(seed, RngStream::Departure),
+            travel_mode: stream_rng(seed, RngStream::TravelMode),
+            knowledge: stream_rng(seed, RngStream::Knowledge),
+        }
+    }
+
+    /// Streams for a random seed, for runs that need not be reproducible
+    pub fn from_entropy() -> Self {
+        Self::new(random_seed())
+    }
+
+    /// Seed the streams were derived from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
@@ -0,0 +1,263 @@
+use super::grid::Grid;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// Policy deciding which shelter an agent heads for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum ShelterChoicePolicy {
+    /// Follow the shared distance field to whichever open shelter is closest
+    #[default]
+    Nearest,
+    /// Nearest shelter whose capacity is not already claimed by occupants and
+    /// by agents heading there
+    CapacityAware,
+    /// Shelter assigned to the zone the agent starts in. `zones` is a raster of
+    /// zone IDs with the grid's dimensions; `assignments` maps zone ID to shelter ID.
+    /// Agents in unassigned zones, or whose shelter is full, go to the nearest one.
+    AssignedByZone {
+        zones: Vec<Vec<u32>>,
+        assignments: HashMap<u32, u32>,
+    },
+    /// Random choice among reachable shelters, weighted by attractiveness
+    /// (shelters missing from the map have weight 1.0)
+    RandomWeighted { attractiveness: HashMap<u32, f64> },
+}
+
+impl ShelterChoicePolicy {
+    /// Whether agents are given an individual target shelter. `Nearest` keeps
+    /// following the shared distance field and needs no per-shelter fields.
+    pub fn uses_targets(&self) -> bool {
+        !matches!(self, ShelterChoicePolicy::Nearest)
+    }
+
+    /// Choose a target shelter for an agent at (x, y) that started out at `origin`.
+    ///
+    /// `pending` counts agents already heading to each shelter, used by the
+    /// capacity-aware policy. Requires `Grid::compute_shelter_distance_fields`.
+    pub fn choose<R: Rng + ?Sized>(
+        &self,
+        grid: &Grid,
+        x: u32,
+        y: u32,
+        origin: (u32, u32),
+        pending: &HashMap<u32, usize>,
+        rng: &mut R,
+    ) -> Option<u32> {
+        // Open shelters reachable from this cell, with their distance
+        let reachable: Vec<(u32, u32)> = grid
+            .shelter_ids()
+            .into_iter()
+            .filter(|&id| grid.shelter_has_space(id))
+            .filter_map(|id| grid.distance_to_shelter_id(id, x, y).map(|dist| (id, dist)))
+            .collect();
+
+        let nearest = |candidates: &[(u32, u32)]| {
+            candidates
+                .iter()
+                .min_by_key(|&&(id, dist)| (dist, id))
+                .map(|&(id, _)| id)
+        };
+
+        match self {
+            ShelterChoicePolicy::Nearest => nearest(&reachable),
+            ShelterChoicePolicy::CapacityAware => {
+                let unclaimed: Vec<(u32, u32)> = reachable
+                    .iter()
+                    .copied()
+                    .filter(|&(id, _)| {
+                        grid.shelter_capacity(id).is_none_or(|capacity| {
+                            grid.shelter_occupancy(id) + pending.get(&id).copied().unwrap_or(0)
+                                < capacity as usize
+                        })
+                    })
+                    .collect();
+                nearest(&unclaimed).or_else(|| nearest(&reachable))
+            }
+            ShelterChoicePolicy::AssignedByZone { zones, assignments } => zones
+                .get(origin.1 as usize)
+                .and_then(|row| row.get(origin.0 as usize))
+                .and_then(|zone| assignments.get(zone))
+                .copied()
+                .filter(|&id| reachable.iter().any(|&(open, _)| open == id))
+                .or_else(|| nearest(&reachable)),
+            ShelterChoicePolicy::RandomWeighted { attractiveness } => {
+                let weights: Vec<f64> = reachable
+                    .iter()
+                    .map(|(id, _)| attractiveness.get(id).copied().unwrap_or(1.0).max(0.0))
+                    .collect();
+                match WeightedIndex::new(&weights) {
+                    Ok(dist) => Some(reachable[dist.sample(rng)].0),
+                    // All weights zero (or no shelters): fall back to the nearest
+                    Err(_) => nearest(&reachable),
+                }
+            }
+        }
+    }
+}
+
+/// Evacuation building or high ground behind a shelter: how high its floors
+/// are, how many people each holds and how fast people get up the stairs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShelterBuilding {
+    /// Height of the ground floor above the ground inundation depth is
+    /// measured from, in metres, e.g. for high ground (default: 0)
+    #[serde(default)]
+    pub elevation: f64,
+    /// Height of one storey in metres (default: 3)
+    #[serde(default = "default_floor_height")]
+    pub floor_height: f64,
+    /// People each floor holds, ground floor first
+    pub floor_capacities: Vec<u32>,
+    /// People per minute the stairs take in (default: unlimited)
+    #[serde(default)]
+    pub stair_throughput: Option<f64>,
+}
+
+fn default_floor_height() -> f64 {
+    3.0
+}
+
+impl ShelterBuilding {
+    /// People the whole building holds
+    pub fn capacity(&self) -> u32 {
+        self.floor_capacities.iter().sum()
+    }
+
+    /// Height of a floor above the ground, in metres
+    pub fn floor_level(&self, floor: usize) -> f64 {
+        self.elevation + floor as f64 * self.floor_height
+    }
+
+    /// Height of the top floor, the deepest water the building can be sheltered from
+    pub fn safe_height(&self) -> f64 {
+        self.floor_level(self.floor_capacities.len().saturating_sub(1))
+    }
+
+    /// Whether people on a floor stay dry at a given inundation depth
+    pub fn floor_is_safe(&self, floor: usize, depth: f64) -> bool {
+        depth <= self.floor_level(floor)
+    }
+
+    /// People the stairs take in per step of `dt` seconds, None if unlimited
+    pub fn stair_capacity_per_step(&self, dt: f64) -> Option<f64> {
+        self.stair_throughput.map(|per_minute| per_minute / 60.0 * dt)
+    }
+}
+
+/// What a shelter is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ShelterKind {
+    HighGround,
+    #[default]
+    Building,
+}
+
+/// Catalogue entry describing a shelter of the grid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShelterInfo {
+    /// Shelter ID, as in the `20NN` grid tokens
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub kind: ShelterKind,
+    /// People the shelter holds, used unless overridden in
+    /// `GridConfig::shelter_capacities` (default: from the grid config)
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Seconds after the start at which the shelter opens (default: 0)
+    #[serde(default)]
+    pub opening_time: f64,
+    /// Whether elders can get in (default: true)
+    #[serde(default = "default_accessible")]
+    pub elder_accessible: bool,
+}
+
+fn default_accessible() -> bool {
+    true
+}
+
+/// Read a shelter catalogue from a JSON array of entries or from a CSV file
+/// with an `id,name,kind,capacity,opening_time,elder_accessible` header, in
+/// any column order. Empty CSV fields take their default; fields in double
+/// quotes may contain commas.
+pub fn load_shelter_catalogue(path: &str) -> io::Result<Vec<ShelterInfo>> {
+    let contents = fs::read_to_string(path)?;
+    if Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+    {
+        parse_shelter_csv(&contents)
+    } else {
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+fn parse_shelter_csv(contents: &str) -> io::Result<Vec<ShelterInfo>> {
+    let invalid = |message: String| io::Error::new(ErrorKind::InvalidData, message);
+    // Quoted fields may hold commas, e.g. "Masjid Raya, Blok A"
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+    let header: Vec<String> = reader
+        .headers()
+        .map_err(|e| invalid(format!("Invalid shelter catalogue header: {}", e)))?
+        .iter()
+        .map(|column| column.to_ascii_lowercase())
+        .collect();
+    if header.iter().all(|column| column.is_empty()) {
+        return Err(invalid("Empty shelter catalogue".to_string()));
+    }
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let id_column = column("id").ok_or_else(|| invalid("Shelter catalogue has no id column".to_string()))?;
+    let name_column = column("name").ok_or_else(|| invalid("Shelter catalogue has no name column".to_string()))?;
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| invalid(format!("Invalid shelter catalogue row: {}", e)))?;
+            let row = record.position().map_or(0, |position| position.line());
+            let field = |index: Option<usize>| {
+                index
+                    .and_then(|index| record.get(index))
+                    .filter(|field| !field.is_empty())
+            };
+            let bad = |name: &str, value: &str| invalid(format!("Row {}: invalid {} '{}'", row, name, value));
+
+            let id = field(Some(id_column)).unwrap_or_default();
+            Ok(ShelterInfo {
+                id: id.parse().map_err(|_| bad("id", id))?,
+                name: field(Some(name_column)).unwrap_or_default().to_string(),
+                kind: match field(column("kind")) {
+                    None => ShelterKind::default(),
+                    Some(kind) => match kind.to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+                        "high_ground" => ShelterKind::HighGround,
+                        "building" => ShelterKind::Building,
+                        _ => return Err(bad("kind", kind)),
+                    },
+                },
+                capacity: field(column("capacity"))
+                    .map(|capacity| capacity.parse().map_err(|_| bad("capacity", capacity)))
+                    .transpose()?,
+                opening_time: field(column("opening_time"))
+                    .map(|time| time.parse().map_err(|_| bad("opening_time", time)))
+                    .transpose()?
+                    .unwrap_or(0.0),
+                elder_accessible: field(column("elder_accessible"))
+                    .map(|value| match value.to_ascii_lowercase().as_str() {
+                        "true" | "yes" | "1" => Ok(true),
+                        "false" | "no" | "0" => Ok(false),
+                        _ => Err(bad("elder_accessible", value)),
+                    })
+                    .transpose()?
+                    .unwrap_or(true),
+            })
+        })
+        .collect()
+}
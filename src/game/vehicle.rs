@@ -0,0 +1,136 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::agent::AgentType;
+
+/// Kinds of vehicle agents can evacuate in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VehicleKind {
+    Car,
+    Motorbike,
+}
+
+/// Vehicle an agent drives, with the people it carries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vehicle {
+    pub kind: VehicleKind,
+    /// Types of the people on board besides the driver
+    pub passengers: Vec<AgentType>,
+}
+
+impl Vehicle {
+    /// People on board, driver included
+    pub fn occupants(&self) -> u32 {
+        1 + self.passengers.len() as u32
+    }
+}
+
+/// Characteristics of one kind of vehicle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleSpec {
+    /// Free driving speed in metres per second
+    pub speed: f64,
+    /// Most people carried, driver included
+    pub occupancy: u32,
+    /// Share of a cell's capacity taken up, in pedestrian equivalents
+    pub footprint: f64,
+}
+
+/// Vehicle evacuation: vehicle characteristics, how many agents drive, and
+/// parking at shelters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleConfig {
+    pub car: VehicleSpec,
+    pub motorbike: VehicleSpec,
+    /// Fraction of agents starting on a road that drive a car and a
+    /// motorbike [car, motorbike] (default: none)
+    pub mode_share: [f64; 2],
+    /// Vehicles each shelter can park (-1 for unlimited)
+    pub shelter_parking: i32,
+    /// Per-shelter parking overrides keyed by shelter ID (-1 for unlimited)
+    #[serde(default)]
+    pub shelter_parking_overrides: HashMap<u32, i32>,
+}
+
+impl Default for VehicleConfig {
+    fn default() -> Self {
+        VehicleConfig {
+            car: VehicleSpec {
+                speed: 8.33,
+                occupancy: 4,
+                footprint: 6.0,
+            },
+            motorbike: VehicleSpec {
+                speed: 6.94,
+                occupancy: 2,
+                footprint: 2.0,
+            },
+            mode_share: [0.0, 0.0],
+            shelter_parking: -1,
+            shelter_parking_overrides: HashMap::new(),
+        }
+    }
+}
+
+impl VehicleConfig {
+    /// Characteristics of a kind of vehicle
+    pub fn spec(&self, kind: VehicleKind) -> &VehicleSpec {
+        match kind {
+            VehicleKind::Car => &self.car,
+            VehicleKind::Motorbike => &self.motorbike,
+        }
+    }
+
+    /// Draw whether an agent drives, and what
+    pub fn sample_mode<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<VehicleKind> {
+        let u: f64 = rng.gen();
+        if u < self.mode_share[0] {
+            Some(VehicleKind::Car)
+        } else if u < self.mode_share[0] + self.mode_share[1] {
+            Some(VehicleKind::Motorbike)
+        } else {
+            None
+        }
+    }
+
+    /// Vehicles a shelter can park, using the per-shelter override if present (None = unlimited)
+    pub fn parking_capacity(&self, shelter_id: u32) -> Option<usize> {
+        let capacity = self
+            .shelter_parking_overrides
+            .get(&shelter_id)
+            .copied()
+            .unwrap_or(self.shelter_parking);
+
+        if capacity < 0 {
+            None
+        } else {
+            Some(capacity as usize)
+        }
+    }
+}
+
+/// Vehicles and the people they carried, counted per kind
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VehicleCounts {
+    pub car: usize,
+    pub motorbike: usize,
+    /// People carried by the counted vehicles
+    pub occupants: usize,
+}
+
+impl VehicleCounts {
+    /// Count one vehicle
+    pub fn add(&mut self, vehicle: &Vehicle) {
+        match vehicle.kind {
+            VehicleKind::Car => self.car += 1,
+            VehicleKind::Motorbike => self.motorbike += 1,
+        }
+        self.occupants += vehicle.occupants() as usize;
+    }
+
+    /// Number of vehicles of every kind
+    pub fn total(&self) -> usize {
+        self.car + self.motorbike
+    }
+}
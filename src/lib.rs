@@ -2,30 +2,45 @@ mod game;
 #[cfg(test)]
 mod tests;
 
-use game::agent::{Agent, AgentType};
+use game::agent::{Agent, AgentConfig, AgentType};
+use game::clock::SimClock;
 use game::game::Model;
-use game::grid::{load_grid_from_ascii, Grid, Terrain};
+use game::grid::{load_grid_from_ascii_with_config, Grid, GridConfig, Terrain};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use rand::Rng;
 use std::io;
 use std::io::{BufRead, BufReader};
 use std::fs::File;
 
 // Re-export important types and modules
 pub use game::agent;
+pub use game::clock;
+pub use game::density;
+pub use game::departure;
+pub use game::fragility;
 pub use game::game as simulation_game; // Renamed to avoid conflict
 pub use game::grid;
+pub use game::household;
+pub use game::knowledge;
+pub use game::rng;
+pub use game::shelter;
+pub use game::vehicle;
 
 // Export API module for web server implementation
 pub mod api;
+pub mod ensemble;
+pub mod sweep;
+pub mod synthetic;
+pub mod tsunami;
 
 /// Configuration for the tsunami simulation parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationConfig {
-    /// Delay in time steps before tsunami starts (default: 30 * 60)
-    pub tsunami_delay: u32,
-    /// Time steps between tsunami propagation updates (default: 28)
-    pub tsunami_speed_time: u32,
+    /// Seconds before tsunami starts (default: 30 * 60)
+    pub tsunami_delay: f64,
+    /// Seconds between tsunami propagation updates (default: 28)
+    pub tsunami_speed_time: f64,
     /// Distribution weights for population distribution (default: [10, 20, 30, 15, 20])
     pub distribution_weights: [i32; 5],
     /// Base movement speed for agents in meters per second (default: 2.66)
@@ -34,20 +49,123 @@ pub struct SimulationConfig {
     pub agent_speed_multipliers: [f64; 4],
     /// Distribution weights for agent types [Child, Teen, Adult, Elder]
     pub agent_type_weights: [f64; 4],
-    /// Interval for collecting agent data (default: 30 steps)
-    pub data_collection_interval: u32,
+    /// Seconds between agent data collections (default: 30)
+    pub data_collection_interval: f64,
+    /// Depth-dependent mortality curves (default: any water is fatal)
+    #[serde(default)]
+    pub fragility: fragility::FragilityConfig,
+    /// Directory of tsunami frames loaded by `Simulation::with_config` (default: none)
+    #[serde(default)]
+    pub tsunami_data_path: Option<String>,
+    /// Generated tsunami frames used when no `tsunami_data_path` is set
+    /// (default: none)
+    #[serde(default)]
+    pub synthetic_tsunami: Option<synthetic::SyntheticTsunami>,
+    /// JSON or CSV catalogue of shelter names and attributes joined to the
+    /// grid's shelters by `Simulation::with_config` (default: none)
+    #[serde(default)]
+    pub shelter_catalogue_path: Option<String>,
+    /// File naming, subsampling and validation of the tsunami frames
+    #[serde(default)]
+    pub tsunami_series: tsunami::TsunamiSeriesConfig,
+    /// How depth is sampled between tsunami frames (default: discrete frames)
+    #[serde(default)]
+    pub tsunami_interpolation: tsunami::FrameInterpolation,
+    /// Depth in metres a cell must exceed to count as flooded in the hazard
+    /// maps (default: 0, any water)
+    #[serde(default)]
+    pub hazard_wet_depth: f32,
+    /// Seed for every random stream; the same seed and inputs give identical
+    /// runs (default: none, a random seed is drawn and recorded here)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Terrain costs, shelter capacities and path planning of the grid
+    #[serde(default)]
+    pub grid: GridConfig,
+    /// Seconds of simulated time per step; every other duration in this
+    /// configuration is converted to steps with it (default: 1)
+    #[serde(default = "default_dt")]
+    pub dt: f64,
+    /// Seconds after the start at which the official warning is issued (default: 0)
+    #[serde(default)]
+    pub warning_time: f64,
+    /// Milling-time distributions between the warning and departure, per
+    /// agent type (default: everyone leaves at the warning)
+    #[serde(default)]
+    pub departure: departure::DepartureConfig,
+    /// Density-dependent walking speed and cell capacity (default: off)
+    #[serde(default)]
+    pub density: density::DensityConfig,
+    /// Cars and motorbikes: speeds, occupancy, mode share and shelter
+    /// parking (default: everyone walks)
+    #[serde(default)]
+    pub vehicles: vehicle::VehicleConfig,
+    /// Household sizes and gathering time; members evacuate together at the
+    /// pace of the slowest (default: everyone on their own)
+    #[serde(default)]
+    pub households: household::HouseholdConfig,
+    /// Share of each agent type that knows the route, and how far the others
+    /// see (default: everyone knows the route)
+    #[serde(default)]
+    pub knowledge: knowledge::KnowledgeConfig,
 }
 
 impl Default for SimulationConfig {
     fn default() -> Self {
         SimulationConfig {
-            tsunami_delay: 30 * 60,
-            tsunami_speed_time: 28,
+            tsunami_delay: 30.0 * 60.0,
+            tsunami_speed_time: 28.0,
             distribution_weights: [10, 20, 30, 15, 20],
             base_speed: 2.66,
             agent_speed_multipliers: [0.8, 1.0, 1.0, 0.7],
             agent_type_weights: [6.21, 13.41, 59.10, 19.89],
-            data_collection_interval: 30,
+            data_collection_interval: 30.0,
+            fragility: fragility::FragilityConfig::default(),
+            tsunami_data_path: None,
+            synthetic_tsunami: None,
+            shelter_catalogue_path: None,
+            tsunami_series: tsunami::TsunamiSeriesConfig::default(),
+            tsunami_interpolation: tsunami::FrameInterpolation::default(),
+            hazard_wet_depth: 0.0,
+            seed: None,
+            grid: GridConfig::default(),
+            dt: default_dt(),
+            warning_time: 0.0,
+            departure: departure::DepartureConfig::default(),
+            density: density::DensityConfig::default(),
+            vehicles: vehicle::VehicleConfig::default(),
+            households: household::HouseholdConfig::default(),
+            knowledge: knowledge::KnowledgeConfig::default(),
+        }
+    }
+}
+
+fn default_dt() -> f64 {
+    1.0
+}
+
+/// Frames are played back `tsunami_speed_time` apart, so frames generated or
+/// read at another interval would move the wave at the wrong speed
+pub(crate) fn check_frame_interval(frame_interval: f64, tsunami_speed_time: f64) -> io::Result<()> {
+    if (frame_interval - tsunami_speed_time).abs() > 1e-9 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Tsunami frames are {} s apart but tsunami_speed_time is {} s",
+                frame_interval, tsunami_speed_time
+            ),
+        ));
+    }
+    Ok(())
+}
+
+impl SimulationConfig {
+    /// Agent speeds and type weights taken from this configuration
+    pub fn agent_config(&self) -> AgentConfig {
+        AgentConfig {
+            base_speed: self.base_speed,
+            speed_multipliers: self.agent_speed_multipliers,
+            type_weights: self.agent_type_weights,
         }
     }
 }
@@ -57,7 +175,7 @@ pub const TSUNAMI_DELAY: u32 = 30 * 60;
 pub const TSUNAMI_SPEED_TIME: u32 = 28;
 pub const DISTRIBUTION_WEIGHTS: [i32; 5] = [10, 20, 30, 15, 20];
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct ShelterAgentTypeData {
     pub child: u32,
     pub teen: u32,
@@ -65,17 +183,6 @@ pub struct ShelterAgentTypeData {
     pub elder: u32,
 }
 
-impl Default for ShelterAgentTypeData {
-    fn default() -> Self {
-        ShelterAgentTypeData {
-            child: 0,
-            teen: 0,
-            adult: 0,
-            elder: 0,
-        }
-    }
-}
-
 #[derive(Serialize, Deserialize)]
 pub struct ShelterData {
     pub step: u32,
@@ -94,10 +201,9 @@ pub struct AgentStatistics {
     pub agent_types: HashMap<String, usize>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AgentDataCollector {
     data: Vec<AgentStepData>,
-    grid: Grid,
 }
 
 #[derive(Clone)]
@@ -107,16 +213,15 @@ pub struct AgentStepData {
     pub id: usize,
     pub agent_type: String,
     pub is_on_road: bool,
-    pub speed: u32,
+    pub speed: f64,
     pub step: u32,
+    /// Seconds elapsed at the start of the step
+    pub time: f64,
 }
 
 impl AgentDataCollector {
-    pub fn new(grid: Grid) -> Self {
-        Self {
-            data: Vec::new(),
-            grid,
-        }
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
     }
 
     pub fn collect_step(&mut self, model: &Model, step: u32) {
@@ -124,7 +229,7 @@ impl AgentDataCollector {
             if agent.is_alive {
                 let real_x = model.grid.xllcorner + (agent.x as f64 * model.grid.cellsize);
                 let real_y = model.grid.yllcorner
-                    + (-1.0 * agent.y as f64 * model.grid.cellsize)
+                    + (-(agent.y as f64) * model.grid.cellsize)
                     + (model.grid.nrow as f64 * model.grid.cellsize);
 
                 self.data.push(AgentStepData {
@@ -135,6 +240,7 @@ impl AgentDataCollector {
                     is_on_road: agent.is_on_road,
                     speed: agent.speed,
                     step,
+                    time: model.clock.time_at(step),
                 });
             }
         }
@@ -152,6 +258,32 @@ pub fn load_population_and_create_agents(
     grid: &mut Grid,
     agents: &mut Vec<Agent>,
     next_agent_id: &mut usize,
+) -> io::Result<()> {
+    load_population_and_create_agents_with_rng(
+        path,
+        ncols,
+        nrows,
+        grid,
+        agents,
+        next_agent_id,
+        &AgentConfig::default(),
+        &mut rand::thread_rng(),
+    )
+}
+
+/// Same as `load_population_and_create_agents`, with agent types and speeds
+/// from `agent_config` and types drawn from `rng`. Each cell gets one agent
+/// per person counted in the population file.
+#[allow(clippy::too_many_arguments)]
+pub fn load_population_and_create_agents_with_rng<R: Rng + ?Sized>(
+    path: &str,
+    ncols: u32,
+    nrows: u32,
+    grid: &mut Grid,
+    agents: &mut Vec<Agent>,
+    next_agent_id: &mut usize,
+    agent_config: &AgentConfig,
+    rng: &mut R,
 ) -> io::Result<()> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
@@ -178,8 +310,7 @@ pub fn load_population_and_create_agents(
     }
 
     if population.len() != nrows as usize {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
+        return Err(io::Error::other(
             "Population data dimensions don't match grid",
         ));
     }
@@ -188,18 +319,18 @@ pub fn load_population_and_create_agents(
 
     for (y, row) in population.iter().enumerate() {
         for (x, &pop) in row.iter().enumerate() {
-            if pop != 0 {
-                let is_on_road = grid.terrain[y][x] == Terrain::Road;
-                let agent_type = AgentType::random();
+            let is_on_road = grid.terrain[y][x] == Terrain::Road;
+            for _ in 0..pop {
+                let agent_type = AgentType::sample_with_weights(&agent_config.type_weights, rng)?;
 
-                let mut agent = Agent::new(
+                let agent = Agent::with_config(
                     *next_agent_id,
                     x as u32,
                     y as u32,
                     agent_type,
                     is_on_road,
+                    agent_config,
                 );
-                agent.remaining_steps = agent.speed;
 
                 grid.add_agent(x as u32, y as u32, agent.id);
                 agents.push(agent);
@@ -211,6 +342,61 @@ pub fn load_population_and_create_agents(
     Ok(())
 }
 
+/// Export the shelters and evacuation signs of a grid as GeoJSON points
+pub fn export_grid_to_geojson(grid: &Grid, filename: &str) -> io::Result<()> {
+    use serde_json::{json, Value};
+    use std::io::Write;
+
+    let shelters = grid.shelters.iter().map(|&(x, y, shelter_id)| {
+        let (real_x, real_y) = grid.cell_coordinates(x, y);
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [real_x, real_y]
+            },
+            "properties": {
+                "kind": "shelter",
+                "shelter_id": shelter_id,
+                "capacity": grid.shelter_capacity(shelter_id),
+                "entrance": grid.is_shelter_entrance(x, y)
+            }
+        })
+    });
+    let signs = grid.signs.iter().map(|&(x, y, heading)| {
+        let (real_x, real_y) = grid.cell_coordinates(x, y);
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [real_x, real_y]
+            },
+            "properties": {
+                "kind": "sign",
+                "heading": heading,
+                "bearing": heading.bearing()
+            }
+        })
+    });
+    let features: Vec<Value> = shelters.chain(signs).collect();
+
+    let geojson = json!({
+        "type": "FeatureCollection",
+        "crs": {
+            "type": "name",
+            "properties": {
+                "name": "EPSG:4326"
+            }
+        },
+        "features": features
+    });
+
+    let mut file = File::create(filename)?;
+    file.write_all(serde_json::to_string_pretty(&geojson)?.as_bytes())?;
+
+    Ok(())
+}
+
 pub fn export_agents_to_geojson(collector: &AgentDataCollector, filename: &str) -> io::Result<()> {
     use serde_json::{json, Value};
     use std::collections::HashMap;
@@ -218,11 +404,13 @@ pub fn export_agents_to_geojson(collector: &AgentDataCollector, filename: &str)
     use std::io::Write;
 
     let mut grouped_data: HashMap<(u32, String), Vec<Vec<f64>>> = HashMap::new();
+    let mut step_times: HashMap<u32, f64> = HashMap::new();
 
     for agent_data in collector.get_data() {
         let key = (agent_data.step, agent_data.agent_type.clone());
-        let coordinates = grouped_data.entry(key).or_insert_with(Vec::new);
+        let coordinates = grouped_data.entry(key).or_default();
         coordinates.push(vec![agent_data.x, agent_data.y]);
+        step_times.insert(agent_data.step, agent_data.time);
     }
 
     let features: Vec<Value> = grouped_data
@@ -236,6 +424,7 @@ pub fn export_agents_to_geojson(collector: &AgentDataCollector, filename: &str)
                 },
                 "properties": {
                     "timestamp": step,
+                    "time": step_times[&step],
                     "agent_type": agent_type
                 }
             })
@@ -288,14 +477,15 @@ pub struct Simulation {
     pub model: Model,
     /// Collects agent data for analysis and visualization
     pub agent_data_collector: AgentDataCollector,
-    /// Current simulation step
-    pub current_step: u32,
     /// Whether tsunami has started
     pub is_tsunami: bool,
     /// Current tsunami propagation index
     pub tsunami_index: usize,
     /// Configuration for the simulation
     pub config: SimulationConfig,
+    /// Arrival time, maximum depth and wet duration per cell, once computed
+    /// by `compute_hazard_maps`
+    pub hazard_maps: Option<tsunami::HazardMaps>,
 }
 
 impl Simulation {
@@ -305,79 +495,198 @@ impl Simulation {
     }
 
     /// Create a new simulation with custom configuration
-    pub fn with_config(grid_path: &str, population_path: &str, config: SimulationConfig) -> io::Result<Self> {
-        let (mut grid, mut agents) = load_grid_from_ascii(grid_path)?;
+    pub fn with_config(grid_path: &str, population_path: &str, mut config: SimulationConfig) -> io::Result<Self> {
+        let seed = *config.seed.get_or_insert_with(rng::random_seed);
+
+        let (mut grid, mut agents) = load_grid_from_ascii_with_config(grid_path, config.grid.clone())?;
+        if let Some(catalogue_path) = &config.shelter_catalogue_path {
+            grid.attach_shelter_catalogue(shelter::load_shelter_catalogue(catalogue_path)?)?;
+        }
+        let agent_config = config.agent_config();
         let mut next_agent_id = agents.len();
+        let first_population_agent = next_agent_id;
+
+        // Agents placed by the grid file move at the configured speeds too
+        for agent in &mut agents {
+            *agent = Agent::with_config(agent.id, agent.x, agent.y, agent.agent_type, agent.is_on_road, &agent_config);
+        }
 
-        load_population_and_create_agents(
+        load_population_and_create_agents_with_rng(
             population_path,
             grid.width,
             grid.height,
             &mut grid,
             &mut agents,
             &mut next_agent_id,
+            &agent_config,
+            &mut rng::stream_rng(seed, rng::RngStream::Population),
         )?;
 
-        let model = Model {
-            grid,
-            agents,
-            dead_agents: 0,
-            dead_agent_types: Vec::new(),
-        };
+        let households = household::form_households(
+            &mut agents,
+            first_population_agent,
+            &config.households,
+            &mut rng::stream_rng(seed, rng::RngStream::Households),
+        );
+
+        if let Some(tsunami_path) = &config.tsunami_data_path {
+            check_frame_interval(config.tsunami_series.frame_interval(), config.tsunami_speed_time)?;
+            tsunami::load_into_grid(tsunami_path, &mut grid, &config.tsunami_series)?;
+        } else if let Some(synthetic) = &config.synthetic_tsunami {
+            check_frame_interval(synthetic.frame_interval, config.tsunami_speed_time)?;
+            synthetic.load_into(&mut grid);
+        }
+
+        let mut model = Model::new(grid, agents);
+        model.fragility = config.fragility.clone();
+        model.tsunami_interpolation = config.tsunami_interpolation;
+        model.clock = SimClock::new(config.dt);
+        model.density = config.density.clone();
+        model.vehicles = config.vehicles.clone();
+        model.households = households;
+        model.gathering_time = config.households.gathering_time;
+        model.knowledge = config.knowledge.clone();
+        model.set_seed(seed);
+        model.assign_route_knowledge();
+        model.assign_travel_modes();
+        model.assign_departure_times(&config.departure, config.warning_time);
 
         Ok(Self {
-            agent_data_collector: AgentDataCollector::new(model.grid.clone()),
+            agent_data_collector: AgentDataCollector::new(),
             model,
-            current_step: 0,
             is_tsunami: false,
             tsunami_index: 0,
             config,
+            hazard_maps: None,
         })
     }
 
-    /// Builder-style method to set configuration
-    pub fn with_tsunami_delay(mut self, delay: u32) -> Self {
+    /// Builder-style method to set the seconds before the tsunami starts
+    pub fn with_tsunami_delay(mut self, delay: f64) -> Self {
         self.config.tsunami_delay = delay;
         self
     }
 
-    /// Builder-style method to set tsunami speed time
-    pub fn with_tsunami_speed_time(mut self, speed_time: u32) -> Self {
+    /// Builder-style method to set the seconds between tsunami frames. Frames
+    /// already loaded, and those loaded later, are taken to be that far apart.
+    pub fn with_tsunami_speed_time(mut self, speed_time: f64) -> Self {
         self.config.tsunami_speed_time = speed_time;
+        let stride = self.config.tsunami_series.frame_stride.max(1);
+        self.config.tsunami_series.frame_time_step = speed_time / stride as f64;
         self
     }
 
-    /// Builder-style method to set data collection interval
-    pub fn with_data_collection_interval(mut self, interval: u32) -> Self {
+    /// Builder-style method to set the seconds between data collections
+    pub fn with_data_collection_interval(mut self, interval: f64) -> Self {
         self.config.data_collection_interval = interval;
         self
     }
 
+    /// Load tsunami frames from a directory using the configured series settings,
+    /// replacing any frames already on the grid
+    pub fn load_tsunami_series(&mut self, dir: &str) -> io::Result<usize> {
+        check_frame_interval(self.config.tsunami_series.frame_interval(), self.config.tsunami_speed_time)?;
+        let frame_count = tsunami::load_into_grid(dir, &mut self.model.grid, &self.config.tsunami_series)?;
+        self.config.tsunami_data_path = Some(dir.to_string());
+        Ok(frame_count)
+    }
+
+    /// Builder-style method to set how depth is sampled between tsunami frames
+    pub fn with_tsunami_interpolation(mut self, mode: tsunami::FrameInterpolation) -> Self {
+        self.config.tsunami_interpolation = mode;
+        self.model.tsunami_interpolation = mode;
+        self
+    }
+
+    /// Fractional tsunami frame position at the current step: the current
+    /// frame index plus the fraction of `tsunami_speed_time` elapsed since it
+    pub fn tsunami_frame_position(&self) -> f64 {
+        if !self.is_tsunami {
+            return 0.0;
+        }
+        let since_delay = self.current_step().saturating_sub(self.delay_steps());
+        since_delay as f64 / self.frame_steps() as f64
+    }
+
+    /// Derive the hazard maps from the loaded tsunami frames, one frame every
+    /// `tsunami_speed_time` seconds, and keep them for `time_until_flooded`
+    pub fn compute_hazard_maps(&mut self) -> io::Result<&tsunami::HazardMaps> {
+        let frame_interval = self.frame_steps() as f64 * self.model.clock.dt;
+        let maps = tsunami::HazardMaps::compute(
+            self.model.grid.tsunami(),
+            frame_interval,
+            self.config.hazard_wet_depth,
+        )?;
+        Ok(self.hazard_maps.insert(maps))
+    }
+
+    /// Seconds from now until (x, y) floods, counting the first frame from
+    /// `tsunami_delay` rounded to whole steps, as `step` does: zero once
+    /// flooded, None if it never floods or the hazard maps have not been computed
+    pub fn time_until_flooded(&self, x: u32, y: u32) -> Option<f64> {
+        let first_frame_time = self.delay_steps() as f64 * self.model.clock.dt;
+        let since_first_frame = self.elapsed_seconds() - first_frame_time;
+        self.hazard_maps.as_ref()?.time_until_flooded(x, y, since_first_frame)
+    }
+
+    /// Index of the next step to run
+    pub fn current_step(&self) -> u32 {
+        self.model.clock.step
+    }
+
+    /// Seconds of simulated time elapsed before the next step
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.model.clock.elapsed()
+    }
+
+    /// Steps before the first tsunami frame arrives
+    fn delay_steps(&self) -> u32 {
+        self.model.clock.steps_for(self.config.tsunami_delay)
+    }
+
+    /// Steps between tsunami frames, at least one
+    fn frame_steps(&self) -> u32 {
+        self.model.clock.steps_for(self.config.tsunami_speed_time).max(1)
+    }
+
+    /// Seed used for this run's random streams
+    pub fn seed(&self) -> u64 {
+        self.model.rng.seed()
+    }
+
+    /// Builder-style method to set the shelter-choice policy
+    pub fn with_shelter_choice(mut self, policy: shelter::ShelterChoicePolicy) -> Self {
+        self.model.set_shelter_choice(policy);
+        self
+    }
+
     /// Run a single simulation step, returns false when simulation should end
     pub fn step(&mut self) -> bool {
         // Return false when simulation should end
-        if !self.model.grid.tsunami_data.is_empty() && 
-           self.tsunami_index > self.model.grid.tsunami_data.len() - 1 {
+        let frame_count = self.model.grid.tsunami_frame_count();
+        if frame_count > 0 && self.tsunami_index > frame_count - 1 {
             return false;
         }
 
-        if self.current_step > self.config.tsunami_delay {
+        let current_step = self.current_step();
+        let clock = self.model.clock;
+        // Frame k arrives `k * tsunami_speed_time` seconds after the delay,
+        // frame 0 at the step the delay ends
+        let delay_steps = self.delay_steps();
+        if current_step >= delay_steps {
             self.is_tsunami = true;
-
-            if self.current_step % self.config.tsunami_speed_time == 0 && 
-               self.current_step != 0 && 
-               self.is_tsunami {
-                self.tsunami_index += 1;
-            }
+            self.tsunami_index = ((current_step - delay_steps) / self.frame_steps()) as usize;
         }
 
-        self.model.step(self.current_step, self.is_tsunami, self.tsunami_index);
+        let frame_position = self.tsunami_frame_position();
+        self.model.step_at(current_step, self.is_tsunami, frame_position);
         
-        if self.current_step % self.config.data_collection_interval == 0 {
-            self.agent_data_collector.collect_step(&self.model, self.current_step);
+        let collection_steps = clock.steps_for(self.config.data_collection_interval).max(1);
+        if current_step.is_multiple_of(collection_steps) {
+            self.agent_data_collector.collect_step(&self.model, current_step);
         }
 
-        self.current_step += 1;
+        self.model.clock.tick();
         true
     }
     
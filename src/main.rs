@@ -1,14 +1,13 @@
-mod game;
-
-use game::agent::{Agent, AgentType};
-use game::game::Model;
-use game::grid::{load_grid_from_ascii, Grid, Terrain};
+use tsunami_simulation::agent::{Agent, AgentType};
+use tsunami_simulation::grid::{load_grid_from_ascii, Grid, Terrain};
+use tsunami_simulation::simulation_game::Model;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 
 use std::fs::File;
 use std::io::{self, BufRead, Write};
+use tsunami_simulation::tsunami::{TsunamiSeries, TsunamiSeriesConfig};
 
 #[derive(Serialize, Deserialize)]
 pub struct ShelterAgentTypeData {
@@ -51,7 +50,7 @@ struct AgentStatistics {
     agent_types: HashMap<String, usize>,
 }
 
-pub fn export_agent_statistics(agents: &Vec<crate::game::agent::Agent>) -> std::io::Result<()> {
+pub fn export_agent_statistics(agents: &Vec<tsunami_simulation::agent::Agent>) -> std::io::Result<()> {
     let mut stats = AgentStatistics {
         total_agents: agents.len(),
         agent_types: HashMap::new(),
@@ -60,10 +59,10 @@ pub fn export_agent_statistics(agents: &Vec<crate::game::agent::Agent>) -> std::
     // Count agents by type
     for agent in agents {
         let agent_type = match agent.agent_type {
-            crate::game::agent::AgentType::Child => "Child",
-            crate::game::agent::AgentType::Teen => "Teen",
-            crate::game::agent::AgentType::Adult => "Adult",
-            crate::game::agent::AgentType::Elder => "Elder",
+            tsunami_simulation::agent::AgentType::Child => "Child",
+            tsunami_simulation::agent::AgentType::Teen => "Teen",
+            tsunami_simulation::agent::AgentType::Adult => "Adult",
+            tsunami_simulation::agent::AgentType::Elder => "Elder",
         };
         *stats.agent_types.entry(agent_type.to_string()).or_insert(0) += 1;
     }
@@ -124,7 +123,6 @@ fn write_grid_to_ascii(filename: &str, model: &Model) -> std::io::Result<()> {
     Ok(())
 }
 
-use rayon::prelude::*;
 use std::{fs, path};
 
 // Structure to store agent data for each step
@@ -251,8 +249,7 @@ fn main() -> io::Result<()> {
 
     let tsunami_data = read_tsunami_data(
         "./data_pacitan/tsunami_pacitan/tsunami_pacitan_2/asc",
-        grid.width,
-        grid.height,
+        &grid,
     )
     .expect("Failed to read tsunami data");
 
@@ -263,12 +260,7 @@ fn main() -> io::Result<()> {
 
     fs::create_dir_all("output").expect("Gagal membuat folder output");
 
-    let mut model = Model {
-        grid,
-        agents,
-        dead_agents: 0,
-        dead_agent_types: Vec::new(),
-    };
+    let mut model = Model::new(grid, agents);
 
     let mut death_json_counter: Vec<serde_json::Value> = Vec::new();
     let mut shelter_json_counter: Vec<serde_json::Value> = Vec::new();
@@ -401,7 +393,7 @@ pub fn load_population_and_create_agents(
     ncols: u32,
     nrows: u32,
     grid: &mut Grid,
-    agents: &mut Vec<crate::game::agent::Agent>,
+    agents: &mut Vec<tsunami_simulation::agent::Agent>,
     next_agent_id: &mut usize,
 ) -> std::io::Result<()> {
     // Buka file dan baca isinya
@@ -447,9 +439,9 @@ pub fn load_population_and_create_agents(
             if pop != 0 {
                 for _ in 0..1 {
                     let is_on_road = grid.terrain[y][x] == Terrain::Road;
-                    let agent_type = crate::game::agent::AgentType::random();
+                    let agent_type = tsunami_simulation::agent::AgentType::random();
     
-                    let mut agent = crate::game::agent::Agent::new(
+                    let mut agent = tsunami_simulation::agent::Agent::new(
                         *next_agent_id,
                         x as u32,
                         y as u32,
@@ -511,94 +503,14 @@ pub fn load_population_from_ascii(path: &str, ncols: u32, nrows: u32) -> io::Res
     Ok(population)
 }
 
-// use std::fs::{self, File};
-use std::path::{Path, PathBuf};
-
-fn read_tsunami_data_file(path: &Path, ncols: u32, nrows: u32) -> io::Result<Vec<Vec<u32>>> {
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
-    let mut lines = reader.lines();
-
-    // Skip header lines
-    for _ in 0..6 {
-        lines.next();
-    }
-
-    let mut tsunami_data = Vec::new();
-
-    for line in lines {
-        let line = line?;
-        let row: Vec<u32> = line
-            .split_whitespace()
-            // .iter()
-            .take(ncols as usize)
-            .filter_map(|token| token.parse::<f64>().ok().map(|val| val as u32))
-            .collect();
-        tsunami_data.push(row);
-    }
-
-    // Fill missing rows with zeros if needed
-    while tsunami_data.len() < nrows as usize {
-        tsunami_data.push(vec![0; ncols as usize]);
-    }
-
-    Ok(tsunami_data)
-}
-
-fn read_tsunami_data(dir_path: &str, ncols: u32, nrows: u32) -> io::Result<Vec<Vec<Vec<u32>>>> {
-    let mut tsunami_files: Vec<PathBuf> = fs::read_dir(dir_path)?
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path())
-        .filter(|path| {
-            path.file_name()
-                .and_then(|name| name.to_str())
-                // .map(|name| name.starts_with("asc_tsunami_pacitan_") && name.ends_with(".asc"))
-                .map(|name| name.ends_with(".asc"))
-                .unwrap_or(false)
-        })
-        .collect();
-
-    // Sort files by their numeric index (keep this sequential for correct ordering)
-    tsunami_files.sort_by_key(|path| {
-        path.file_name()
-            .and_then(|name| name.to_str())
-            .and_then(|name| {
-                name.trim_start_matches("aav_rep_z_04_")
-                    .trim_end_matches(".asc")
-                    .parse::<u32>()
-                    .ok()
-            })
-            .unwrap_or(0)
-    });
-
-    tsunami_files = tsunami_files
-        .into_iter()
-        .enumerate()
-        .filter(|(i, _)| i % 4 == 0) // Keep 1st, 5th, 9th, etc.
-        .map(|(_, path)| path)
-        .collect();
-
-    // Process tsunami data files in parallel
-    let all_tsunami_data: Vec<_> = tsunami_files
-        .par_iter()
-        .filter_map(
-            |file_path| match read_tsunami_data_file(file_path, ncols, nrows) {
-                Ok(data) => Some(data),
-                Err(e) => {
-                    eprintln!("Error reading tsunami file {:?}: {}", file_path, e);
-                    None
-                }
-            },
-        )
-        .collect();
-
-    if all_tsunami_data.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "No valid tsunami data files found",
-        ));
-    }
+/// Every fourth tsunami frame in `dir_path`, ordered by the index in its file
+/// name, after checking each header against `grid`
+fn read_tsunami_data(dir_path: &str, grid: &Grid) -> io::Result<Vec<Vec<Vec<f32>>>> {
+    let config = TsunamiSeriesConfig {
+        frame_stride: 4,
+        ..TsunamiSeriesConfig::default()
+    };
 
-    Ok(all_tsunami_data)
+    Ok(TsunamiSeries::load(dir_path, grid, &config)?.frames)
 }
 
@@ -0,0 +1,466 @@
+//! Parameter sweeps over `SimulationConfig` fields with one-at-a-time and
+//! first-order (Sobol-style) sensitivity indices
+
+use crate::ensemble::Ensemble;
+use crate::rng::{self, RngStream};
+use crate::SimulationConfig;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Write};
+
+/// A config field to vary, named by its dotted path in the serialized
+/// `SimulationConfig`, e.g. `tsunami_delay`, `agent_speed_multipliers.3` or
+/// `grid.blocked_penalty`. Integer fields are rounded to the nearest value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterRange {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// How sample points are placed in the parameter space
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SweepDesign {
+    /// Full factorial grid with `levels` evenly spaced values per parameter
+    Grid { levels: usize },
+    /// Latin hypercube of `samples` points
+    LatinHypercube { samples: usize },
+    /// A baseline at the midpoint of every range, plus `levels` values of each
+    /// parameter in turn with the others at the baseline
+    OneAtATime { levels: usize },
+}
+
+/// Runs a scenario at many points of a parameter space
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    pub grid_path: String,
+    pub population_path: String,
+    /// Configuration the swept fields are applied to
+    pub base_config: SimulationConfig,
+    pub parameters: Vec<ParameterRange>,
+    /// Placement of sample points (default: Latin hypercube of 20 points)
+    pub design: SweepDesign,
+    /// Seeded replicates averaged per point (default: 1)
+    pub replicates: usize,
+    /// Seed for the sample points and, shared by every point, the replicates
+    pub seed: u64,
+    /// Step limit per run (default: run until the tsunami frames end)
+    pub max_steps: Option<u32>,
+}
+
+/// Parameter values of one sample point and the outcome metrics there
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepPoint {
+    /// Value of each parameter, in the order of `Sweep::parameters`
+    pub values: Vec<f64>,
+    /// Parameter varied away from the baseline in a one-at-a-time design
+    /// (None for the baseline and for other designs)
+    pub varied: Option<usize>,
+    pub metrics: BTreeMap<String, f64>,
+}
+
+/// Sensitivity of one metric to one parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensitivityIndex {
+    pub parameter: String,
+    pub metric: String,
+    /// Share of the metric's variance explained by the parameter alone
+    pub first_order: f64,
+    /// Range of the metric when only this parameter moves (one-at-a-time designs)
+    pub oat_swing: Option<f64>,
+}
+
+/// Sample points of a sweep with their metrics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepResult {
+    pub parameters: Vec<ParameterRange>,
+    pub points: Vec<SweepPoint>,
+}
+
+impl Sweep {
+    pub fn new(grid_path: &str, population_path: &str, base_config: SimulationConfig) -> Self {
+        let seed = base_config.seed.unwrap_or_else(rng::random_seed);
+        Sweep {
+            grid_path: grid_path.to_string(),
+            population_path: population_path.to_string(),
+            base_config,
+            parameters: Vec::new(),
+            design: SweepDesign::LatinHypercube { samples: 20 },
+            replicates: 1,
+            seed,
+            max_steps: None,
+        }
+    }
+
+    /// Builder-style method to add a parameter to vary between `min` and `max`
+    pub fn with_parameter(mut self, name: &str, min: f64, max: f64) -> Self {
+        self.parameters.push(ParameterRange {
+            name: name.to_string(),
+            min,
+            max,
+        });
+        self
+    }
+
+    /// Builder-style method to set the sampling design
+    pub fn with_design(mut self, design: SweepDesign) -> Self {
+        self.design = design;
+        self
+    }
+
+    /// Builder-style method to set the replicates averaged per point
+    pub fn with_replicates(mut self, replicates: usize) -> Self {
+        self.replicates = replicates;
+        self
+    }
+
+    /// Builder-style method to set the seed
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Builder-style method to set the step limit per run
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sample points of the design, as parameter values and the parameter
+    /// varied in a one-at-a-time design
+    pub fn sample_points(&self) -> Vec<(Vec<f64>, Option<usize>)> {
+        let ranges = &self.parameters;
+        let at = |range: &ParameterRange, fraction: f64| range.min + (range.max - range.min) * fraction;
+        let level = |k: usize, levels: usize| {
+            if levels <= 1 {
+                0.5
+            } else {
+                k as f64 / (levels - 1) as f64
+            }
+        };
+
+        match self.design {
+            SweepDesign::Grid { levels } => {
+                let levels = levels.max(1);
+                let mut points = vec![Vec::new()];
+                for range in ranges {
+                    points = points
+                        .into_iter()
+                        .flat_map(|point: Vec<f64>| {
+                            (0..levels).map(move |k| {
+                                let mut point = point.clone();
+                                point.push(at(range, level(k, levels)));
+                                point
+                            })
+                        })
+                        .collect();
+                }
+                points.into_iter().map(|point| (point, None)).collect()
+            }
+            SweepDesign::LatinHypercube { samples } => {
+                let mut rng = rng::stream_rng(self.seed, RngStream::Sweep);
+                let mut columns: Vec<Vec<f64>> = Vec::with_capacity(ranges.len());
+                for range in ranges {
+                    // One point in each of `samples` equal strata, in random order
+                    let mut strata: Vec<usize> = (0..samples).collect();
+                    strata.shuffle(&mut rng);
+                    columns.push(
+                        strata
+                            .into_iter()
+                            .map(|stratum| at(range, (stratum as f64 + rng.gen::<f64>()) / samples as f64))
+                            .collect(),
+                    );
+                }
+                (0..samples)
+                    .map(|i| (columns.iter().map(|column| column[i]).collect(), None))
+                    .collect()
+            }
+            SweepDesign::OneAtATime { levels } => {
+                let baseline: Vec<f64> = ranges.iter().map(|range| at(range, 0.5)).collect();
+                let mut points = vec![(baseline.clone(), None)];
+                for (i, range) in ranges.iter().enumerate() {
+                    for k in 0..levels {
+                        let mut point = baseline.clone();
+                        point[i] = at(range, level(k, levels));
+                        points.push((point, Some(i)));
+                    }
+                }
+                points
+            }
+        }
+    }
+
+    /// Configuration for a point, with each parameter applied to the base config
+    pub fn config_at(&self, values: &[f64]) -> io::Result<SimulationConfig> {
+        self.parameters
+            .iter()
+            .zip(values)
+            .try_fold(self.base_config.clone(), |config, (range, &value)| {
+                set_config_value(&config, &range.name, value)
+            })
+    }
+
+    /// Run every sample point in parallel
+    pub fn run(&self) -> io::Result<SweepResult> {
+        let points = self
+            .sample_points()
+            .into_par_iter()
+            .map(|(values, varied)| {
+                let config = self.config_at(&values)?;
+                let mut ensemble = Ensemble::new(&self.grid_path, &self.population_path, config)
+                    .with_replicates(self.replicates.max(1))
+                    .with_base_seed(self.seed);
+                ensemble.max_steps = self.max_steps;
+                let result = ensemble.run()?;
+
+                let replicates = &result.replicates;
+                let count = replicates.len() as f64;
+                let mut metrics = BTreeMap::new();
+                if let Some(mean) = result.summary.total_deaths.mean {
+                    metrics.insert("deaths".to_string(), mean);
+                }
+                metrics.insert(
+                    "death_rate".to_string(),
+                    replicates
+                        .iter()
+                        .map(|r| r.total_deaths as f64 / r.total_agents.max(1) as f64)
+                        .sum::<f64>()
+                        / count,
+                );
+                metrics.insert(
+                    "sheltered".to_string(),
+                    replicates.iter().map(|r| r.evacuation_steps.len() as f64).sum::<f64>() / count,
+                );
+                for (name, stat) in &result.summary.deaths_by_type {
+                    if let Some(mean) = stat.mean {
+                        metrics.insert(format!("deaths.{}", name), mean);
+                    }
+                }
+                for (name, stat) in &result.summary.evacuation_time_percentiles {
+                    if let Some(mean) = stat.mean {
+                        metrics.insert(format!("evacuation_time.{}", name), mean);
+                    }
+                }
+
+                Ok(SweepPoint {
+                    values,
+                    varied,
+                    metrics,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(SweepResult {
+            parameters: self.parameters.clone(),
+            points,
+        })
+    }
+}
+
+/// Copy of `config` with the numeric field at a dotted path set to `value`
+pub fn set_config_value(config: &SimulationConfig, path: &str, value: f64) -> io::Result<SimulationConfig> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let mut json = serde_json::to_value(config)?;
+    let field = path
+        .split('.')
+        .try_fold(&mut json, |node, key| match node {
+            Value::Object(fields) => fields.get_mut(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+            _ => None,
+        })
+        .ok_or_else(|| invalid(format!("Unknown config field: {}", path)))?;
+
+    *field = match field {
+        Value::Number(number) if number.is_f64() => serde_json::json!(value),
+        Value::Number(_) => serde_json::json!(value.round() as i64),
+        _ => return Err(invalid(format!("Config field {} is not numeric", path))),
+    };
+
+    serde_json::from_value(json).map_err(|e| invalid(format!("Invalid value {} for {}: {}", value, path, e)))
+}
+
+impl SweepResult {
+    /// Names of the metrics recorded at any point, in sorted order
+    pub fn metric_names(&self) -> Vec<String> {
+        let names: BTreeSet<&String> = self.points.iter().flat_map(|point| point.metrics.keys()).collect();
+        names.into_iter().cloned().collect()
+    }
+
+    /// First-order index of each parameter for a metric, estimated from the
+    /// sample points: the variance of the metric's mean within bins of the
+    /// parameter, over the metric's total variance. Points where the metric is
+    /// undefined are skipped.
+    pub fn first_order_indices(&self, metric: &str) -> Vec<f64> {
+        let samples: Vec<(&[f64], f64)> = self
+            .points
+            .iter()
+            .filter_map(|point| {
+                let value = *point.metrics.get(metric)?;
+                value.is_finite().then_some((point.values.as_slice(), value))
+            })
+            .collect();
+
+        let n = samples.len();
+        let mean = samples.iter().map(|&(_, y)| y).sum::<f64>() / n.max(1) as f64;
+        let total_variance: f64 = samples.iter().map(|&(_, y)| (y - mean).powi(2)).sum();
+
+        (0..self.parameters.len())
+            .map(|i| {
+                if n < 2 || total_variance <= 0.0 {
+                    return 0.0;
+                }
+
+                let mut sorted: Vec<(f64, f64)> = samples.iter().map(|&(x, y)| (x[i], y)).collect();
+                sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                // Grid-like designs repeat each value: bin by value. Otherwise
+                // use about sqrt(n) bins of equal size.
+                let mut distinct: Vec<f64> = sorted.iter().map(|&(x, _)| x).collect();
+                distinct.dedup();
+                let bins: Vec<Vec<f64>> = if distinct.len() * 2 <= n {
+                    distinct
+                        .iter()
+                        .map(|&value| sorted.iter().filter(|&&(x, _)| x == value).map(|&(_, y)| y).collect())
+                        .collect()
+                } else {
+                    let bin_count = ((n as f64).sqrt().round() as usize).max(2);
+                    (0..bin_count)
+                        .map(|b| sorted[b * n / bin_count..(b + 1) * n / bin_count].iter().map(|&(_, y)| y).collect())
+                        .collect()
+                };
+
+                let explained: f64 = bins
+                    .iter()
+                    .filter(|bin| !bin.is_empty())
+                    .map(|bin| {
+                        let bin_mean = bin.iter().sum::<f64>() / bin.len() as f64;
+                        bin.len() as f64 * (bin_mean - mean).powi(2)
+                    })
+                    .sum();
+                (explained / total_variance).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+
+    /// Range of a metric over the baseline and the points varying only that
+    /// parameter, for one-at-a-time designs (None otherwise)
+    pub fn one_at_a_time(&self, metric: &str) -> Vec<Option<f64>> {
+        let baseline = self
+            .points
+            .iter()
+            .find(|point| point.varied.is_none())
+            .and_then(|point| point.metrics.get(metric).copied());
+
+        (0..self.parameters.len())
+            .map(|i| {
+                let values: Vec<f64> = self
+                    .points
+                    .iter()
+                    .filter(|point| point.varied == Some(i))
+                    .filter_map(|point| point.metrics.get(metric).copied())
+                    .chain(baseline)
+                    .filter(|value| value.is_finite())
+                    .collect();
+                if values.len() < 2 {
+                    return None;
+                }
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                Some(max - min)
+            })
+            .collect()
+    }
+
+    /// Every metric's sensitivity to every parameter
+    pub fn sensitivity(&self) -> Vec<SensitivityIndex> {
+        self.metric_names()
+            .into_iter()
+            .flat_map(|metric| {
+                let first_order = self.first_order_indices(&metric);
+                let oat = self.one_at_a_time(&metric);
+                self.parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(i, range)| SensitivityIndex {
+                        parameter: range.name.clone(),
+                        metric: metric.clone(),
+                        first_order: first_order[i],
+                        oat_swing: oat[i],
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Write the points, their metrics and the sensitivity indices as JSON
+    pub fn write_json(&self, path: &str) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(
+            file,
+            &serde_json::json!({
+                "parameters": self.parameters,
+                "points": self.points,
+                "sensitivity": self.sensitivity(),
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Write one row per point: parameter values followed by metrics
+    pub fn write_csv(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        let metrics = self.metric_names();
+
+        let header: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|range| range.name.clone())
+            .chain(std::iter::once("varied".to_string()))
+            .chain(metrics.iter().cloned())
+            .collect();
+        writeln!(file, "{}", header.join(","))?;
+
+        for point in &self.points {
+            let row: Vec<String> = point
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .chain(std::iter::once(
+                    point
+                        .varied
+                        .map(|i| self.parameters[i].name.clone())
+                        .unwrap_or_default(),
+                ))
+                .chain(metrics.iter().map(|metric| {
+                    point.metrics.get(metric).map(|value| value.to_string()).unwrap_or_default()
+                }))
+                .collect();
+            writeln!(file, "{}", row.join(","))?;
+        }
+
+        Ok(())
+    }
+
+    /// Write one row per parameter and metric with its sensitivity indices
+    pub fn write_sensitivity_csv(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "parameter,metric,first_order,oat_swing")?;
+        for index in self.sensitivity() {
+            writeln!(
+                file,
+                "{},{},{},{}",
+                index.parameter,
+                index.metric,
+                index.first_order,
+                index.oat_swing.map(|swing| swing.to_string()).unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,31 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use std::io;
     use std::path::Path;
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
     use std::fs;
     use std::sync::Once;
 
     use crate::*;
-    use crate::grid::{GridConfig, Terrain, load_grid_from_ascii, load_grid_from_ascii_with_config};
+    use crate::grid::{FloodRouting, GridConfig, ShelterEntrances, SignHeading, Terrain, TerrainDensities, load_grid_from_ascii, load_grid_from_ascii_with_config};
     use crate::agent::{Agent, AgentType, AgentConfig};
+    use crate::clock::SimClock;
+    use crate::shelter::{load_shelter_catalogue, ShelterBuilding, ShelterChoicePolicy, ShelterInfo, ShelterKind};
+    use crate::vehicle::{VehicleConfig, VehicleKind};
+    use crate::household::{form_households, HouseholdConfig};
+    use crate::knowledge::{KnowledgeConfig, RouteKnowledge};
+    use crate::departure::{DepartureConfig, DepartureDistribution};
+    use crate::density::{DensityConfig, FundamentalDiagram};
+    use crate::rng::{stream_rng, RngStream};
+    use crate::ensemble::{Ensemble, EnsembleResult, Statistic};
+    use crate::synthetic::{GridEdge, Shoreline, SyntheticTsunami};
+    use crate::sweep::{set_config_value, ParameterRange, Sweep, SweepDesign, SweepPoint, SweepResult};
+    use crate::fragility::{FragilityConfig, FragilityCurve, HazardOutcome};
+    use crate::tsunami::{
+        self, FrameInterpolation, MaxDepthEnvelope, StreamingTsunami, TsunamiSeries, TsunamiSeriesConfig, TsunamiSource,
+        TsunamiSourceHandle,
+    };
 
     // Use this to ensure test data is only cleaned up at the end of all tests
     static CLEANUP: Once = Once::new();
@@ -154,13 +171,14 @@ NODATA_value -9999
             allow_diagonal: true,
             shelter_capacity: 100,
             path_algorithm: "bfs".to_string(),
+            ..GridConfig::default()
         };
         
         let (grid, _) = load_grid_from_ascii_with_config(&grid_path, config.clone())?;
         
         // Check that config was applied
         assert_eq!(grid.config.blocked_penalty, 5);
-        assert_eq!(grid.config.allow_diagonal, true);
+        assert!(grid.config.allow_diagonal);
         assert_eq!(grid.config.shelter_capacity, 100);
         assert_eq!(grid.config.path_algorithm, "bfs");
         
@@ -214,7 +232,7 @@ NODATA_value -9999
         
         // Create 1000 random agents
         for _ in 0..1000 {
-            let agent_type = AgentType::random_with_weights(&config.type_weights);
+            let agent_type = AgentType::random_with_weights(&config.type_weights).unwrap();
             let key = match agent_type {
                 AgentType::Child => "Child",
                 AgentType::Teen => "Teen",
@@ -237,8 +255,238 @@ NODATA_value -9999
             assert!(*count > 150); // Each should be roughly 250 (25%)
             assert!(*count < 350);
         }
+
+        // Weights no type can be drawn from, as a sweep may produce, are an error
+        let error = AgentType::random_with_weights(&[0.0; 4]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
     }
     
+    // Test that a seed fixes every random stream of a run
+    #[test]
+    fn test_seeded_runs_are_reproducible() -> io::Result<()> {
+        let grid_path = create_test_grid("seeded")?;
+        let pop_path = create_test_population("seeded")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("seeded", "wave_", 6, header)?;
+
+        let run = |seed: u64| -> io::Result<_> {
+            let config = SimulationConfig {
+                tsunami_data_path: Some(tsunami_dir.clone()),
+                fragility: FragilityConfig::lognormal(3.0, 0.8),
+                seed: Some(seed),
+                ..SimulationConfig::default()
+            };
+            let mut simulation = Simulation::with_config(&grid_path, &pop_path, config)?
+                .with_tsunami_delay(2.0)
+                .with_tsunami_speed_time(2.0)
+                .with_shelter_choice(ShelterChoicePolicy::RandomWeighted {
+                    attractiveness: HashMap::new(),
+                });
+            simulation.run(Some(12))?;
+
+            let snapshot: Vec<_> = simulation
+                .model
+                .agents
+                .iter()
+                .map(|a| (a.id, a.x, a.y, a.agent_type, a.fragility_quantile))
+                .collect();
+            Ok((snapshot, simulation.model.dead_agent_types.clone(), simulation.seed()))
+        };
+
+        let first = run(7)?;
+        assert_eq!(first.2, 7);
+        assert_eq!(run(7)?, first);
+        assert_ne!(run(8)?.0, first.0);
+
+        // Subsystem streams of one seed are independent of each other
+        let mut population = stream_rng(7, RngStream::Population);
+        let mut hazard = stream_rng(7, RngStream::Hazard);
+        assert_ne!(population.gen::<u64>(), hazard.gen::<u64>());
+        let mut again = stream_rng(7, RngStream::Population);
+        let types: Vec<_> = (0..20).map(|_| AgentType::sample(&mut again)).collect();
+        let mut population = stream_rng(7, RngStream::Population);
+        assert_eq!(types, (0..20).map(|_| AgentType::sample(&mut population)).collect::<Vec<_>>());
+
+        // Without a seed one is drawn and recorded
+        let simulation = Simulation::new(&grid_path, &pop_path)?;
+        assert_eq!(simulation.config.seed, Some(simulation.seed()));
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test seeded replicates and their aggregated statistics
+    #[test]
+    fn test_ensemble_runner() -> io::Result<()> {
+        let grid_path = create_test_grid("ensemble")?;
+        let pop_path = create_test_population("ensemble")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("ensemble", "wave_", 4, header)?;
+
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir),
+            tsunami_delay: 2.0,
+            tsunami_speed_time: 2.0,
+            tsunami_series: TsunamiSeriesConfig::default().with_frame_time_step(2.0),
+            data_collection_interval: 3.0,
+            fragility: FragilityConfig::lognormal(2.0, 0.8),
+            ..SimulationConfig::default()
+        };
+        let ensemble = Ensemble::new(&grid_path, &pop_path, config)
+            .with_replicates(4)
+            .with_base_seed(100);
+        let result = ensemble.run()?;
+
+        let seeds: Vec<u64> = result.replicates.iter().map(|r| r.seed).collect();
+        assert_eq!(seeds, vec![100, 101, 102, 103]);
+        assert_eq!(result, ensemble.run()?);
+
+        let summary = &result.summary;
+        assert_eq!(summary.replicates, 4);
+        let mean_deaths = result.replicates.iter().map(|r| r.total_deaths as f64).sum::<f64>() / 4.0;
+        assert!((summary.total_deaths.mean.unwrap() - mean_deaths).abs() < 1e-9);
+        assert!(summary.total_deaths.ci_lower.unwrap() <= mean_deaths);
+        assert!(mean_deaths <= summary.total_deaths.ci_upper.unwrap());
+        for replicate in &result.replicates {
+            assert_eq!(replicate.deaths_by_type.values().sum::<usize>(), replicate.total_deaths);
+            assert!(replicate.total_deaths <= replicate.total_agents);
+            assert_eq!(replicate.cumulative_arrivals.last(), Some(&replicate.evacuation_steps.len()));
+        }
+        assert_eq!(summary.arrival_steps[1], 3);
+        assert!(summary.evacuation_time_percentiles.contains_key("p50"));
+
+        fs::create_dir_all("test_output")?;
+        result.write_json("test_output/ensemble.json")?;
+        result.write_csv("test_output/ensemble.csv")?;
+        let csv = fs::read_to_string("test_output/ensemble.csv")?;
+        assert!(csv.starts_with("metric,samples,mean,std_dev,ci_lower,ci_upper,min,max"));
+        assert!(csv.contains("deaths.Elder,4,"));
+        assert!(csv.contains("evacuation_time.p90,"));
+
+        // Known values: mean 2, sample std dev 1, t(2) = 4.303
+        let stat = Statistic::from_samples(&[1.0, 2.0, 3.0]);
+        assert_eq!(stat.mean, Some(2.0));
+        assert_eq!(stat.std_dev, Some(1.0));
+        assert!((stat.ci_upper.unwrap() - (2.0 + 4.303 / 3f64.sqrt())).abs() < 1e-9);
+
+        // Quantities no replicate observed have no values and survive a JSON round trip
+        let empty = Statistic::from_samples(&[]);
+        assert_eq!(empty.mean, None);
+        let json = serde_json::to_string(&empty)?;
+        assert_eq!(serde_json::from_str::<Statistic>(&json)?, empty);
+        let written: EnsembleResult = serde_json::from_str(&fs::read_to_string("test_output/ensemble.json")?)?;
+        assert_eq!(written.summary.deaths_by_type.len(), result.summary.deaths_by_type.len());
+        assert_eq!(written.replicates.len(), 4);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test parameter sweeps and sensitivity indices
+    #[test]
+    fn test_parameter_sweep() -> io::Result<()> {
+        // Named fields are set through their serialized paths
+        let base = SimulationConfig::default();
+        let config = set_config_value(&base, "tsunami_delay", 12.6)?;
+        assert_eq!(config.tsunami_delay, 12.6);
+        let config = set_config_value(&config, "tsunami_series.frame_stride", 2.6)?;
+        assert_eq!(config.tsunami_series.frame_stride, 3);
+        let config = set_config_value(&config, "grid.blocked_penalty", 5.0)?;
+        assert_eq!(config.grid.blocked_penalty, 5);
+        let config = set_config_value(&config, "agent_speed_multipliers.3", 0.5)?;
+        assert_eq!(config.agent_speed_multipliers, [0.8, 1.0, 1.0, 0.5]);
+        assert!(set_config_value(&base, "no_such_field", 1.0).is_err());
+        assert!(set_config_value(&base, "grid.path_algorithm", 1.0).is_err());
+        assert!(set_config_value(&base, "tsunami_series.frame_stride", -5.0).is_err());
+
+        let grid_path = create_test_grid("sweep")?;
+        let pop_path = create_test_population("sweep")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("sweep", "wave_", 4, header)?;
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir),
+            tsunami_speed_time: 10.0,
+            fragility: FragilityConfig::step_thresholds(),
+            tsunami_series: TsunamiSeriesConfig::default().with_frame_time_step(10.0),
+            // One step covers a 5 m cell at 1 m/s
+            dt: 5.0,
+            ..SimulationConfig::default()
+        };
+
+        // The swept speed reaches the agents
+        let fast = set_config_value(&config, "base_speed", 5.0)?;
+        let simulation = Simulation::with_config(&grid_path, &pop_path, fast)?;
+        assert!(simulation
+            .model
+            .agents
+            .iter()
+            .all(|a| a.speed == Agent::with_config(0, 0, 0, a.agent_type, true, &simulation.config.agent_config()).speed));
+        assert!(simulation.model.agents.iter().any(|a| a.speed >= 3.5));
+
+        let sweep = Sweep::new(&grid_path, &pop_path, config)
+            .with_parameter("tsunami_delay", 0.0, 40.0)
+            .with_parameter("base_speed", 1.0, 3.0)
+            .with_seed(11);
+
+        let grid_points = sweep.clone().with_design(SweepDesign::Grid { levels: 3 }).sample_points();
+        assert_eq!(grid_points.len(), 9);
+        assert!(grid_points.iter().any(|(values, _)| values == &vec![20.0, 3.0]));
+
+        let lhs_points = sweep.clone().with_design(SweepDesign::LatinHypercube { samples: 5 }).sample_points();
+        assert_eq!(lhs_points.len(), 5);
+        for i in 0..2 {
+            let range = &sweep.parameters[i];
+            let mut strata: Vec<usize> = lhs_points
+                .iter()
+                .map(|(values, _)| ((values[i] - range.min) / (range.max - range.min) * 5.0) as usize)
+                .collect();
+            strata.sort();
+            assert_eq!(strata, vec![0, 1, 2, 3, 4]);
+        }
+
+        let result = sweep.with_design(SweepDesign::OneAtATime { levels: 3 }).run()?;
+        assert_eq!(result.points.len(), 7);
+        assert!(result.points.iter().all(|p| p.metrics.contains_key("deaths")));
+        let sensitivity = result.sensitivity();
+        assert_eq!(sensitivity.len(), 2 * result.metric_names().len());
+        // Metrics missing at some points, such as evacuation times where nobody
+        // was sheltered, may have no swing
+        assert!(sensitivity.iter().filter(|index| index.metric == "deaths").all(|index| index.oat_swing.is_some()));
+
+        fs::create_dir_all("test_output")?;
+        result.write_csv("test_output/sweep.csv")?;
+        result.write_sensitivity_csv("test_output/sweep_sensitivity.csv")?;
+        let csv = fs::read_to_string("test_output/sweep.csv")?;
+        assert!(csv.starts_with("tsunami_delay,base_speed,varied,"));
+        assert_eq!(csv.lines().count(), 8);
+
+        // A metric driven by one parameter alone is attributed to it
+        let mut points: Vec<SweepPoint> = (0..16)
+            .map(|i| SweepPoint {
+                values: vec![(i % 4) as f64, (i / 4) as f64],
+                varied: None,
+                metrics: [("deaths".to_string(), 10.0 * (i % 4) as f64)].into_iter().collect(),
+            })
+            .collect();
+        // A metric recorded at only some points is still listed
+        points[5].metrics.insert("arrival_time".to_string(), 60.0);
+        let synthetic = SweepResult {
+            parameters: vec![
+                ParameterRange { name: "a".to_string(), min: 0.0, max: 3.0 },
+                ParameterRange { name: "b".to_string(), min: 0.0, max: 3.0 },
+            ],
+            points,
+        };
+        let indices = synthetic.first_order_indices("deaths");
+        assert!((indices[0] - 1.0).abs() < 1e-9);
+        assert!(indices[1].abs() < 1e-9);
+        assert_eq!(synthetic.one_at_a_time("deaths"), vec![None, None]);
+        assert_eq!(synthetic.metric_names(), vec!["arrival_time", "deaths"]);
+
+        clean_test_data();
+        Ok(())
+    }
+
     // Test agent speed calculation based on type
     #[test]
     fn test_agent_speed_calculation() {
@@ -247,21 +495,856 @@ NODATA_value -9999
             speed_multipliers: [0.5, 1.0, 1.5, 0.75],
             type_weights: [25.0, 25.0, 25.0, 25.0],
         };
-        
-        let child_agent = Agent::with_config(0, 0, 0, AgentType::Child, true, &config);
-        let teen_agent = Agent::with_config(1, 0, 0, AgentType::Teen, true, &config);
-        let adult_agent = Agent::with_config(2, 0, 0, AgentType::Adult, true, &config);
-        let elder_agent = Agent::with_config(3, 0, 0, AgentType::Elder, true, &config);
-        let custom_agent = Agent::with_config(4, 0, 0, AgentType::Custom(1.25), true, &config);
-        
-        // Check that speeds match expected values
-        assert_eq!(child_agent.speed, 1); // 2.0 * 0.5 = 1.0
-        assert_eq!(teen_agent.speed, 2);  // 2.0 * 1.0 = 2.0
-        assert_eq!(adult_agent.speed, 3); // 2.0 * 1.5 = 3.0
-        assert_eq!(elder_agent.speed, 1); // 2.0 * 0.75 = 1.5, truncated to 1
-        assert_eq!(custom_agent.speed, 2); // 2.0 * 1.25 = 2.5, truncated to 2
+        
+        let child_agent = Agent::with_config(0, 0, 0, AgentType::Child, true, &config);
+        let teen_agent = Agent::with_config(1, 0, 0, AgentType::Teen, true, &config);
+        let adult_agent = Agent::with_config(2, 0, 0, AgentType::Adult, true, &config);
+        let elder_agent = Agent::with_config(3, 0, 0, AgentType::Elder, true, &config);
+        let custom_agent = Agent::with_config(4, 0, 0, AgentType::Custom(1.25), true, &config);
+        
+        // Check that speeds match expected values
+        assert_eq!(child_agent.speed, 1.0); // 2.0 * 0.5
+        assert_eq!(teen_agent.speed, 2.0);  // 2.0 * 1.0
+        assert_eq!(adult_agent.speed, 3.0); // 2.0 * 1.5
+        assert_eq!(elder_agent.speed, 1.5); // 2.0 * 0.75
+        assert_eq!(custom_agent.speed, 2.5); // 2.0 * 1.25
+
+        // Speeds are converted to cells through the cell size and step duration
+        assert_eq!(elder_agent.cells_per_step(5.0, 1.0), 0.3);
+        assert_eq!(custom_agent.cells_per_step(5.0, 2.0), 1.0);
+        assert_eq!(adult_agent.cells_per_step(1.5, 1.0), 2.0);
+    }
+    
+    // Test density-dependent walking speed and shared cells
+    #[test]
+    fn test_density_dependent_speed() -> io::Result<()> {
+        let weidmann = FundamentalDiagram::weidmann();
+        assert_eq!(weidmann.speed_factor(0.0), 1.0);
+        assert_eq!(weidmann.speed_factor(5.4), 0.0);
+        assert!(weidmann.speed_factor(0.5) > weidmann.speed_factor(2.0));
+        assert_eq!(FundamentalDiagram::Fruin.speed_factor(0.1), 1.0);
+        assert_eq!(FundamentalDiagram::Fruin.speed_factor(1.5), 0.55);
+        assert_eq!(FundamentalDiagram::Fruin.speed_factor(3.0), 0.2);
+        let table = FundamentalDiagram::Table {
+            points: vec![(0.0, 1.0), (2.0, 0.5), (4.0, 0.1)],
+        };
+        assert_eq!(table.speed_factor(1.0), 0.75);
+        assert_eq!(table.speed_factor(9.0), 0.1);
+        // Tables are built from distinct, finite densities
+        assert_eq!(FundamentalDiagram::table(vec![(4.0, 0.1), (0.0, 1.0), (2.0, 0.5)])?, table);
+        let duplicate = vec![(0.0, 1.0), (2.0, 0.5), (2.0, 0.3)];
+        assert!(FundamentalDiagram::table(duplicate.clone()).is_err());
+        assert!(FundamentalDiagram::table(vec![(f64::NEG_INFINITY, 1.0)]).is_err());
+        let stepped = FundamentalDiagram::Table { points: duplicate };
+        assert!((0..40).all(|i| (0.0..=1.0).contains(&stepped.speed_factor(i as f64 * 0.1))));
+
+        // Without a diagram cells hold one agent; with one, as many as fit
+        assert_eq!(DensityConfig::default().cell_capacity(5.0), 1);
+        assert_eq!(DensityConfig::with_diagram(FundamentalDiagram::Fruin).cell_capacity(2.0), 21);
+
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("density_grid.asc");
+        let grid_content = format!(
+            "ncols 30\nnrows 1\nxllcorner 100.0\nyllcorner 200.0\ncellsize 1.0\nNODATA_value -9999\n{} 2001",
+            vec!["1"; 29].join(" ")
+        );
+        fs::write(&grid_path, grid_content)?;
+
+        let walk = |crowd: usize| -> io::Result<simulation_game::Model> {
+            let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+            let agents: Vec<Agent> = (0..crowd).map(|id| Agent::new(id, 0, 0, AgentType::Adult, true)).collect();
+            for agent in &agents {
+                grid.add_agent(agent.x, agent.y, agent.id);
+            }
+            let mut model = simulation_game::Model::new(grid, agents);
+            model.density = DensityConfig::with_diagram(FundamentalDiagram::weidmann());
+            assert_eq!(model.cell_capacity(0, 0), 5);
+            assert!((model.grid.local_density(0, 0, 1) - crowd as f64 / 2.0).abs() < 1e-9);
+            for step in 0..3 {
+                model.step(step, false, 0);
+                assert!(model.grid.agents_in_cell[0].iter().all(|cell| cell.len() <= 5));
+            }
+            Ok(model)
+        };
+
+        // A lone agent walks almost freely, a crowd of five is held back
+        let alone = walk(1)?;
+        let crowd = walk(5)?;
+        let lead = crowd.agents.iter().map(|a| a.x).max().unwrap();
+        assert!(alone.agents[0].x >= 7);
+        assert!(lead < alone.agents[0].x);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test per-terrain cell capacity in movement and reservations
+    #[test]
+    fn test_cell_capacity() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("cell_capacity_grid.asc");
+        let grid_content = "ncols 12
+nrows 1
+xllcorner 100.0
+yllcorner 200.0
+cellsize 2.0
+NODATA_value -9999
+1 1 1 1 1 1 1 1 1 1 1 2001";
+        fs::write(&grid_path, grid_content)?;
+
+        let walk = |cell_densities: Option<TerrainDensities>| -> io::Result<usize> {
+            let config = GridConfig {
+                cell_densities,
+                ..GridConfig::default()
+            };
+            let (mut grid, _) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config)?;
+            let agents: Vec<Agent> = (0..4).map(|id| Agent::new(id, 0, 0, AgentType::Adult, true)).collect();
+            for agent in &agents {
+                grid.add_agent(agent.x, agent.y, agent.id);
+            }
+            let mut model = simulation_game::Model::new(grid, agents);
+            model.clock.dt = 2.0;
+            for step in 0..5 {
+                model.step(step, false, 0);
+                // Road cells past the start hold at most their capacity
+                for (x, cell) in model.grid.agents_in_cell[0].iter().enumerate().take(11).skip(1) {
+                    assert!(cell.len() <= model.cell_capacity(x as u32, 0));
+                }
+            }
+            Ok(model.shelter_arrivals.len())
+        };
+
+        // One person per m² on 2 m cells lets four agents share a road cell
+        let mut densities = TerrainDensities::uniform(1.0);
+        densities.custom = 0.1;
+        let (mut grid, _) = load_grid_from_ascii_with_config(
+            &grid_path.to_string_lossy(),
+            GridConfig {
+                cell_densities: Some(densities.clone()),
+                ..GridConfig::default()
+            },
+        )?;
+        assert_eq!(grid.cell_capacity(0, 0), Some(4));
+        grid.terrain[0][1] = Terrain::Custom(1.5);
+        assert_eq!(grid.cell_capacity(1, 0), Some(1));
+
+        // A group starting in one cell walks together instead of queueing
+        assert!(walk(None)? < 4);
+        assert_eq!(walk(Some(densities))?, 4);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test vehicle agents on the road network, parking and casualties
+    #[test]
+    fn test_vehicle_agents() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("vehicle_grid.asc");
+        let grid_content = "ncols 10
+nrows 2
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+1 1 1 1 1 1 1 1 1 2001
+c1.0 c1.0 c1.0 c1.0 c1.0 c1.0 c1.0 c1.0 c1.0 c1.0";
+        fs::write(&grid_path, grid_content)?;
+
+        let load_model = |vehicles: VehicleConfig| -> io::Result<simulation_game::Model> {
+            let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+            let agents = vec![
+                Agent::new(0, 0, 0, AgentType::Adult, true),
+                Agent::new(1, 0, 0, AgentType::Child, true),
+                Agent::new(2, 0, 0, AgentType::Elder, true),
+                Agent::new(3, 0, 1, AgentType::Adult, false),
+            ];
+            for agent in &agents {
+                grid.add_agent(agent.x, agent.y, agent.id);
+            }
+            let mut model = simulation_game::Model::new(grid, agents);
+            model.vehicles = vehicles;
+            model.set_seed(5);
+            model.assign_travel_modes();
+            Ok(model)
+        };
+
+        // Everyone on a road drives a car; agents off the road walk. The
+        // first driver takes the two others in its cell on board, so nobody
+        // is added or lost.
+        let drive_all = VehicleConfig {
+            mode_share: [1.0, 0.0],
+            ..VehicleConfig::default()
+        };
+        let mut model = load_model(drive_all.clone())?;
+        assert_eq!(model.agents.len(), 2);
+        assert_eq!(model.agents[0].vehicle.as_ref().map(|v| v.kind), Some(VehicleKind::Car));
+        assert_eq!(model.agents[0].speed, 8.33);
+        assert_eq!(model.agents[0].people(), 3);
+        // Passengers keep their own types
+        let passengers = model.agents[0].vehicle.as_ref().map(|v| v.passengers.clone());
+        assert_eq!(passengers, Some(vec![AgentType::Child, AgentType::Elder]));
+        assert!(model.agents[1].vehicle.is_none());
+        assert_eq!(model.agents.iter().map(|a| a.people()).sum::<u32>(), 4);
+        assert_eq!(model.grid.agents_in_cell[0][0], vec![0]);
+
+        // The car stays on the road and parks with all three occupants
+        for step in 0..8 {
+            model.step(step, false, 0);
+            assert_eq!(model.agents[0].y, 0);
+        }
+        assert_eq!(model.grid.shelter_occupancy(1), 3);
+        assert_eq!(model.parked_vehicles[&1].car, 1);
+        assert_eq!(model.parked_vehicles[&1].occupants, 3);
+        assert_eq!(model.shelter_arrivals[0].people(), 3);
+        let sheltered: Vec<AgentType> = model.grid.shelter_agents[&1].iter().map(|&(_, t)| t).collect();
+        assert_eq!(sheltered, vec![AgentType::Adult, AgentType::Child, AgentType::Elder]);
+
+        // No parking space: the car is turned away at the shelter
+        let mut model = load_model(VehicleConfig {
+            shelter_parking: 0,
+            ..drive_all.clone()
+        })?;
+        for step in 0..8 {
+            model.step(step, false, 0);
+        }
+        assert_eq!(model.grid.shelter_occupancy(1), 0);
+        assert!(model.parked_vehicles.is_empty());
+
+        // A vehicle caught by the water takes its occupants with it; the
+        // dead are the four people placed
+        let mut model = load_model(drive_all)?;
+        model.grid.tsunami_data = vec![vec![vec![1.0; 10]; 2]];
+        model.step(0, true, 0);
+        assert_eq!(model.dead_agents, 4);
+        let dead = [AgentType::Adult, AgentType::Adult, AgentType::Child, AgentType::Elder];
+        assert_eq!(model.dead_agent_types, dead);
+        assert_eq!(model.dead_vehicles.car, 1);
+        assert_eq!(model.dead_vehicles.occupants, 3);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test households gathering and evacuating together
+    #[test]
+    fn test_household_evacuation() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("household_grid.asc");
+        let grid_content = "ncols 10
+nrows 1
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+1 1 1 1 1 1 1 1 1 2001";
+        fs::write(&grid_path, grid_content)?;
+
+        let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+        let agent_config = AgentConfig::default();
+        let mut agents: Vec<Agent> = [AgentType::Adult, AgentType::Elder, AgentType::Elder, AgentType::Adult]
+            .into_iter()
+            .enumerate()
+            .map(|(id, agent_type)| Agent::with_config(id, 0, 0, agent_type, true, &agent_config))
+            .collect();
+        for agent in &agents {
+            grid.add_agent(0, 0, agent.id);
+        }
+
+        // Households of three: the cell's four agents split into one
+        // household and one agent on its own
+        let config = HouseholdConfig {
+            size_weights: vec![0.0, 0.0, 1.0],
+            gathering_time: 10.0,
+        };
+        let households = form_households(&mut agents, 0, &config, &mut stream_rng(3, RngStream::Households));
+        assert_eq!(agents.len(), 4);
+        assert_eq!(households.len(), 1);
+        assert_eq!(households[0].members, vec![0, 1, 2]);
+        assert_eq!(agents[3].household, None);
+        // Members walk at the elders' pace
+        assert!(agents[..3].iter().all(|a| a.speed == 0.7 * 2.66 && a.household == Some(0)));
+        assert_eq!(agents[3].speed, 2.66);
+
+        let mut model = simulation_game::Model::new(grid, agents);
+        model.households = households;
+        model.gathering_time = config.gathering_time;
+        model.clock.dt = 5.0;
+        model.set_seed(3);
+        model.assign_departure_times(&DepartureConfig::default(), 10.0);
+        // Warning at 10 s plus 10 s of gathering
+        assert!(model.agents[..3].iter().all(|a| a.departure_step == 4));
+
+        for step in 0..20 {
+            model.step(step, false, 0);
+            let cells: Vec<(u32, u32)> = model
+                .agents
+                .iter()
+                .filter(|a| a.household == Some(0))
+                .map(|a| (a.x, a.y))
+                .collect();
+            assert!(cells.iter().all(|&cell| cell == cells[0]), "members split up at step {}", step);
+            if step < 4 {
+                assert_eq!(cells[0], (0, 0));
+            }
+        }
+
+        let outcomes = model.household_outcomes();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!((outcomes[0].size, outcomes[0].sheltered, outcomes[0].dead), (3, 3, 0));
+
+        // Two people fit in a cell: a household of two cannot join someone
+        // standing in the next cell, even though its leader alone could
+        let (mut grid, _) = load_grid_from_ascii_with_config(
+            &grid_path.to_string_lossy(),
+            GridConfig {
+                cell_densities: Some(TerrainDensities::uniform(0.08)),
+                ..GridConfig::default()
+            },
+        )?;
+        let mut agents: Vec<Agent> = [0, 0, 1]
+            .into_iter()
+            .enumerate()
+            .map(|(id, x)| Agent::new(id, x, 0, AgentType::Adult, true))
+            .collect();
+        agents[0].household = Some(0);
+        agents[1].household = Some(0);
+        agents[2].departure_step = u32::MAX;
+        for agent in &agents {
+            grid.add_agent(agent.x, agent.y, agent.id);
+        }
+        let mut model = simulation_game::Model::new(grid, agents);
+        assert_eq!(model.cell_capacity(1, 0), 2);
+        model.set_seed(3);
+        for step in 0..5 {
+            model.step(step, false, 0);
+        }
+        let cells: Vec<u32> = model.agents.iter().map(|a| a.x).collect();
+        assert_eq!(cells, vec![0, 0, 1]);
+
+        // The two people counted in one cell of the population file become
+        // two agents there, who form a household of two
+        let grid_path = create_test_grid("household_population")?;
+        let pop_path = create_test_population("household_population")?;
+        let config = SimulationConfig {
+            households: HouseholdConfig {
+                size_weights: vec![0.0, 1.0],
+                gathering_time: 0.0,
+            },
+            seed: Some(3),
+            ..SimulationConfig::default()
+        };
+        let simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+        let model = &simulation.model;
+        let residents: Vec<usize> = model.agents.iter().filter(|a| (a.x, a.y) == (5, 7)).map(|a| a.id).collect();
+        assert_eq!(residents.len(), 2);
+        assert_eq!(model.households.len(), 1);
+        assert_eq!(model.households[0].members, residents);
+        assert_eq!(model.households[0].home, (5, 7));
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test shelter routes diverting around flooded roads
+    #[test]
+    fn test_flood_rerouting() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("reroute_grid.asc");
+        let grid_content = "ncols 9
+nrows 1
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+2002 1 1 1 1 1 1 1 2001";
+        fs::write(&grid_path, grid_content)?;
+
+        // The road between the agent and the nearest shelter floods
+        let run_with = |config: GridConfig| -> io::Result<simulation_game::Model> {
+            let (mut grid, _) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config)?;
+            let mut wave = vec![vec![0.0; 9]];
+            wave[0][6] = 1.0;
+            grid.tsunami_data = vec![wave];
+            grid.add_agent(5, 0, 0);
+            let mut model = simulation_game::Model::new(grid, vec![Agent::new(0, 5, 0, AgentType::Adult, true)]);
+            model.set_seed(9);
+            assert_eq!(model.grid.distance_to_shelter[0][5], Some(3));
+            for step in 0..30 {
+                model.step(step, true, 0);
+            }
+            Ok(model)
+        };
+        let run = |flood_routing: FloodRouting| {
+            run_with(GridConfig {
+                flood_routing,
+                ..GridConfig::default()
+            })
+        };
+
+        // Routes that ignore the water lead the agent to its death
+        let model = run(FloodRouting::Ignore)?;
+        assert_eq!(model.dead_agents, 1);
+
+        // Flooded cells are cut out: the agent turns back to the far shelter
+        let model = run(FloodRouting::Impassable { min_depth: 0.5 })?;
+        assert!(model.grid.is_flooded(6, 0));
+        assert_eq!(model.grid.distance_to_shelter[0][5], Some(5));
+        assert_eq!(model.grid.plan_path_astar(5, 0).map(|plan| plan.shelter_id), Some(2));
+        assert_eq!(model.dead_agents, 0);
+        assert_eq!(model.grid.shelter_occupancy(2), 1);
+
+        // Water below the threshold does not change the routes
+        let model = run(FloodRouting::Impassable { min_depth: 2.0 })?;
+        assert!(!model.grid.is_flooded(6, 0));
+        assert_eq!(model.dead_agents, 1);
+
+        // A high enough penalty diverts the agent as well
+        let model = run(FloodRouting::Penalty { min_depth: 0.0, cost: 10 })?;
+        assert_eq!(model.grid.distance_to_shelter[0][7], Some(1));
+        assert_eq!(model.dead_agents, 0);
+
+        // Breadth-first search cannot weigh the penalty, so the shortest routes
+        // are still found with Dijkstra
+        let model = run_with(GridConfig {
+            flood_routing: FloodRouting::Penalty { min_depth: 0.0, cost: 10 },
+            path_algorithm: "bfs".to_string(),
+            ..GridConfig::default()
+        })?;
+        assert_eq!(model.grid.distance_to_shelter[0][5], Some(5));
+        assert_eq!(model.dead_agents, 0);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test agents that do not know the route finding their way from what they see
+    #[test]
+    fn test_route_knowledge() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("knowledge_grid.asc");
+        let grid_content = "ncols 9
+nrows 1
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+2002 1 1 1 1 1 1 1 2001";
+        fs::write(&grid_path, grid_content)?;
+
+        let knowledge = KnowledgeConfig {
+            informed_share: [0.0; 4],
+            perception_radius: 2,
+        };
+        assert_eq!(knowledge.sample(AgentType::Adult, &mut stream_rng(1, RngStream::Knowledge)), RouteKnowledge::Local);
+        assert!(KnowledgeConfig::default().everyone_informed());
+
+        // An uninformed agent at x = 3 walking west, optionally with an
+        // informed neighbour at x = 5 heading east to shelter 1
+        let run = |with_neighbour: bool| -> io::Result<simulation_game::Model> {
+            let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+            let mut lost = Agent::new(0, 3, 0, AgentType::Adult, true);
+            lost.knowledge = RouteKnowledge::Local;
+            lost.heading = Some((-1, 0));
+            grid.add_agent(3, 0, 0);
+            let mut agents = vec![lost];
+            if with_neighbour {
+                // An id that is not its index, as after earlier agents died
+                let mut guide = Agent::new(2, 5, 0, AgentType::Adult, true);
+                guide.heading = Some((1, 0));
+                grid.add_agent(5, 0, 2);
+                agents.push(guide);
+            }
+            let mut model = simulation_game::Model::new(grid, agents);
+            model.knowledge = knowledge.clone();
+            model.set_seed(4);
+            for step in 0..40 {
+                model.step(step, false, 0);
+            }
+            Ok(model)
+        };
+
+        // Alone, it keeps going the way it was walking until it sees shelter 2
+        let model = run(false)?;
+        assert_eq!(model.shelter_arrivals.len(), 1);
+        assert_eq!(model.shelter_arrivals[0].shelter_id, 2);
+        assert_eq!(model.shelter_arrivals[0].knowledge, RouteKnowledge::Local);
+
+        // With an informed neighbour in sight, it turns and follows it to shelter 1
+        let model = run(true)?;
+        assert_eq!(model.shelter_arrivals.len(), 2);
+        assert!(model.shelter_arrivals.iter().all(|arrival| arrival.shelter_id == 1));
+
+        // A household knows the route if any member does
+        let (grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+        let mut agents: Vec<Agent> = (0..3).map(|id| Agent::new(id, 4, 0, AgentType::Adult, true)).collect();
+        agents[2].agent_type = AgentType::Elder;
+        for agent in &mut agents[1..] {
+            agent.household = Some(0);
+        }
+        let mut model = simulation_game::Model::new(grid, agents);
+        model.knowledge = KnowledgeConfig {
+            informed_share: [0.0, 0.0, 0.0, 1.0],
+            ..KnowledgeConfig::default()
+        };
+        model.set_seed(4);
+        model.assign_route_knowledge();
+        let levels: Vec<RouteKnowledge> = model.agents.iter().map(|a| a.knowledge).collect();
+        assert_eq!(levels, vec![RouteKnowledge::Local, RouteKnowledge::Informed, RouteKnowledge::Informed]);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test evacuation signs in the grid file, their use and their export
+    #[test]
+    fn test_signage() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("signage_grid.asc");
+        let header = "ncols 9\nnrows 1\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999\n";
+        fs::write(&grid_path, format!("{}2002 1 1 sW 1 1 1 1 2001", header))?;
+
+        let (grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+        assert_eq!(grid.signs, vec![(3, 0, SignHeading::W)]);
+        assert_eq!(grid.terrain[0][3], Terrain::Road);
+        assert_eq!(grid.sign_at(3, 0), Some(SignHeading::W));
+        assert_eq!(grid.sign_at(4, 0), None);
+
+        // An uninformed agent walking east turns back where the sign points
+        let run = |grid: grid::Grid| -> simulation_game::Model {
+            let mut grid = grid;
+            let mut agent = Agent::new(0, 4, 0, AgentType::Adult, true);
+            agent.knowledge = RouteKnowledge::Local;
+            agent.heading = Some((1, 0));
+            grid.add_agent(4, 0, 0);
+            let mut model = simulation_game::Model::new(grid, vec![agent]);
+            model.knowledge.perception_radius = 2;
+            model.set_seed(6);
+            for step in 0..40 {
+                model.step(step, false, 0);
+            }
+            model
+        };
+        let model = run(grid.clone());
+        assert_eq!(model.shelter_arrivals[0].shelter_id, 2);
+
+        let mut unsigned = grid.clone();
+        unsigned.signs.clear();
+        let model = run(unsigned);
+        assert_eq!(model.shelter_arrivals[0].shelter_id, 1);
+
+        // Shelters and signs are exported as points
+        let geojson_path = test_dir.join("signage_grid.geojson");
+        export_grid_to_geojson(&grid, &geojson_path.to_string_lossy())?;
+        let geojson: serde_json::Value = serde_json::from_str(&fs::read_to_string(&geojson_path)?)?;
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        let sign = features.iter().find(|f| f["properties"]["kind"] == "sign").unwrap();
+        assert_eq!(sign["properties"]["bearing"], 270.0);
+        assert_eq!(sign["geometry"]["coordinates"], serde_json::json!([115.0, 205.0]));
+
+        // Unknown headings are rejected
+        fs::write(&grid_path, format!("{}2002 1 1 sQ 1 1 1 1 2001", header))?;
+        assert!(load_grid_from_ascii(&grid_path.to_string_lossy()).is_err());
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test evacuation buildings: floors flooding in turn and stair throughput
+    #[test]
+    fn test_vertical_evacuation() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("vertical_grid.asc");
+        let grid_content = "ncols 4
+nrows 1
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+1 1 1 2001";
+        fs::write(&grid_path, grid_content)?;
+
+        let load_model = |building: ShelterBuilding| -> io::Result<simulation_game::Model> {
+            let mut config = GridConfig::default();
+            config.shelter_buildings.insert(1, building);
+            let (mut grid, _) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config)?;
+            let agents: Vec<Agent> = (0..3)
+                .map(|id| Agent::new(id, 2 - id as u32, 0, AgentType::Adult, true))
+                .collect();
+            for agent in &agents {
+                grid.add_agent(agent.x, agent.y, agent.id);
+            }
+            let mut model = simulation_game::Model::new(grid, agents);
+            model.set_seed(8);
+            Ok(model)
+        };
+
+        // Two storeys of one person each: the first arrival climbs to the top
+        let building = ShelterBuilding {
+            elevation: 0.0,
+            floor_height: 3.0,
+            floor_capacities: vec![1, 1],
+            stair_throughput: None,
+        };
+        assert_eq!(building.safe_height(), 3.0);
+        let mut model = load_model(building)?;
+        assert_eq!(model.grid.shelter_capacity(1), Some(2));
+        for step in 0..20 {
+            model.step(step, false, 0);
+        }
+        let people_per_floor = |model: &simulation_game::Model| -> Vec<usize> {
+            model.shelter_floors[&1].iter().map(|floor| floor.iter().map(|o| o.people).sum()).collect()
+        };
+        assert_eq!(people_per_floor(&model), vec![1, 1]);
+        assert_eq!(model.shelter_floors[&1][1][0].agent_id, 0);
+
+        // 2 m of water drowns the ground floor only; 4 m tops the building
+        let mut shallow = vec![vec![0.0; 4]];
+        shallow[0][3] = 2.0;
+        let mut deep = vec![vec![0.0; 4]];
+        deep[0][3] = 4.0;
+        model.grid.tsunami_data = vec![shallow, deep];
+        model.step(20, true, 0);
+        assert_eq!(model.dead_agents, 1);
+        assert_eq!(people_per_floor(&model), vec![0, 1]);
+        // Only the drowned agent leaves the shelter and its cell
+        assert_eq!(model.grid.shelter_agents[&1], vec![(0, AgentType::Adult)]);
+        assert_eq!(model.grid.agents_in_cell[0][3], vec![0]);
+        assert!(model.grid.failed_shelters.is_empty());
+        model.step(21, true, 1);
+        assert_eq!(model.dead_agents, 2);
+        assert!(model.grid.failed_shelters.contains(&1));
+        assert!(!model.grid.shelter_has_space(1));
+        assert_eq!(model.shelter_failures.len(), 2);
+        assert_eq!((model.shelter_failures[1].floor, model.shelter_failures[1].deaths), (1, 1));
+        // The agent left outside was never in the water
+        assert_eq!(model.agents.len(), 1);
+
+        // Stairs taking 6 people a minute admit one person every 10 s
+        let mut model = load_model(ShelterBuilding {
+            elevation: 0.0,
+            floor_height: 3.0,
+            floor_capacities: vec![10],
+            stair_throughput: Some(6.0),
+        })?;
+        model.clock.dt = 5.0;
+        for step in 0..20 {
+            model.step(step, false, 0);
+        }
+        let steps: Vec<u32> = model.shelter_arrivals.iter().map(|a| a.step).collect();
+        assert_eq!(steps.len(), 3);
+        assert!(steps[0] >= 1);
+        assert!(steps.windows(2).all(|w| w[1] - w[0] >= 2), "arrivals {:?}", steps);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test loading a shelter catalogue and joining it to the grid
+    #[test]
+    fn test_shelter_catalogue() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+
+        // CSV columns in any order, empty fields take their default and
+        // quoted fields may hold commas
+        let csv_path = test_dir.join("catalogue_shelters.csv");
+        fs::write(
+            &csv_path,
+            "name,id,kind,capacity,opening_time,elder_accessible\n\"Balai Desa, Blok A\",1,high-ground,25,,no\nMasjid,2,building,,60,\n",
+        )?;
+        let catalogue = load_shelter_catalogue(&csv_path.to_string_lossy())?;
+        assert_eq!(
+            catalogue[0],
+            ShelterInfo {
+                id: 1,
+                name: "Balai Desa, Blok A".to_string(),
+                kind: ShelterKind::HighGround,
+                capacity: Some(25),
+                opening_time: 0.0,
+                elder_accessible: false,
+            }
+        );
+        assert_eq!((catalogue[1].capacity, catalogue[1].opening_time, catalogue[1].elder_accessible), (None, 60.0, true));
+        fs::write(&csv_path, "id,name,kind\n1,Balai Desa,tower\n")?;
+        assert!(load_shelter_catalogue(&csv_path.to_string_lossy()).is_err());
+
+        // IDs must match the shelters of the grid both ways
+        let grid_path = create_test_grid("catalogue")?;
+        let (mut grid, _) = load_grid_from_ascii(&grid_path)?;
+        let err = grid.attach_shelter_catalogue(catalogue.clone()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(grid.attach_shelter_catalogue(Vec::new()).is_err());
+        assert_eq!(grid.shelter_label(1), "shelter_1");
+
+        let json_path = test_dir.join("catalogue_shelters.json");
+        fs::write(&json_path, r#"[{"id": 1, "name": "Balai Desa", "capacity": 25}]"#)?;
+        let config = SimulationConfig {
+            shelter_catalogue_path: Some(json_path.to_string_lossy().to_string()),
+            seed: Some(1),
+            ..SimulationConfig::default()
+        };
+        let pop_path = create_test_population("catalogue")?;
+        let simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+        let grid = &simulation.model.grid;
+        assert_eq!(grid.shelter_label(1), "Balai Desa");
+        assert_eq!(grid.shelter_info(1).map(|info| info.kind), Some(ShelterKind::Building));
+        assert_eq!(grid.shelter_capacity(1), Some(25));
+
+        // Shelters admit nobody before they open, and no elders if inaccessible
+        let row_path = test_dir.join("catalogue_row.asc");
+        fs::write(&row_path, "ncols 3\nnrows 1\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999\n1 1 2001")?;
+        let (mut grid, _) = load_grid_from_ascii(&row_path.to_string_lossy())?;
+        grid.attach_shelter_catalogue(vec![ShelterInfo {
+            id: 1,
+            name: "Balai Desa".to_string(),
+            kind: ShelterKind::Building,
+            capacity: None,
+            opening_time: 10.0,
+            elder_accessible: false,
+        }])?;
+        let agents = vec![
+            Agent::new(0, 1, 0, AgentType::Adult, true),
+            Agent::new(1, 0, 0, AgentType::Elder, true),
+        ];
+        for agent in &agents {
+            grid.add_agent(agent.x, agent.y, agent.id);
+        }
+        let mut model = simulation_game::Model::new(grid, agents);
+        model.set_seed(2);
+        for step in 0..30 {
+            model.step(step, false, 0);
+        }
+        assert_eq!(model.shelter_arrivals.len(), 1);
+        assert_eq!(model.shelter_arrivals[0].agent_type, AgentType::Adult);
+        assert!(model.shelter_arrivals[0].time >= 10.0);
+
+        // An elder turned away at the nearest shelter goes on to one that lets them in
+        fs::write(&row_path, "ncols 6\nnrows 1\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999\n2001 1 1 1 1 2002")?;
+        let (mut grid, _) = load_grid_from_ascii(&row_path.to_string_lossy())?;
+        let info = |id: u32, elder_accessible: bool| ShelterInfo {
+            id,
+            name: format!("Shelter {}", id),
+            kind: ShelterKind::Building,
+            capacity: None,
+            opening_time: 0.0,
+            elder_accessible,
+        };
+        grid.attach_shelter_catalogue(vec![info(1, false), info(2, true)])?;
+        grid.add_agent(2, 0, 0);
+        let mut model = simulation_game::Model::new(grid, vec![Agent::new(0, 2, 0, AgentType::Elder, true)]);
+        model.set_seed(2);
+        for step in 0..30 {
+            model.step(step, false, 0);
+        }
+        assert_eq!(model.shelter_arrivals.len(), 1);
+        assert_eq!(model.shelter_arrivals[0].shelter_id, 2);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test the simulation clock and time stamps on output records
+    #[test]
+    fn test_simulation_clock() -> io::Result<()> {
+        let clock = SimClock::new(2.0);
+        assert_eq!(clock.steps_for(30.0), 15);
+        assert_eq!(clock.steps_for(-4.0), 0);
+        assert_eq!(clock.time_at(7), 14.0);
+
+        let grid_path = create_test_grid("clock")?;
+        let pop_path = create_test_population("clock")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("clock", "wave_", 4, header)?;
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir),
+            dt: 2.0,
+            tsunami_delay: 6.0,
+            tsunami_speed_time: 4.0,
+            tsunami_series: TsunamiSeriesConfig::default().with_frame_time_step(4.0),
+            data_collection_interval: 4.0,
+            ..SimulationConfig::default()
+        };
+        let mut simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+
+        // 6 s before the tsunami is 3 steps of 2 s: frame 0 arrives at step 3
+        for _ in 0..3 {
+            simulation.step();
+        }
+        assert!(!simulation.is_tsunami);
+        simulation.step();
+        assert!(simulation.is_tsunami);
+        assert_eq!(simulation.tsunami_index, 0);
+        assert_eq!(simulation.current_step(), 4);
+        assert_eq!(simulation.elapsed_seconds(), 8.0);
+
+        // Data is collected every 4 s and stamped with both step and time
+        let mut collected: Vec<(u32, f64)> = simulation
+            .agent_data_collector
+            .get_data()
+            .iter()
+            .map(|data| (data.step, data.time))
+            .collect();
+        collected.dedup();
+        assert_eq!(collected, vec![(0, 0.0), (2, 4.0)]);
+
+        simulation.run(Some(20))?;
+        assert!(simulation
+            .model
+            .shelter_arrivals
+            .iter()
+            .all(|arrival| arrival.time == arrival.step as f64 * 2.0));
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test fractional speeds accumulating sub-cell progress
+    #[test]
+    fn test_fractional_movement() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+
+        let walk = |cellsize: f64| -> io::Result<(u32, u32)> {
+            let grid_path = test_dir.join(format!("fractional_{}_grid.asc", cellsize));
+            let grid_content = format!(
+                "ncols 12\nnrows 2\nxllcorner 100.0\nyllcorner 200.0\ncellsize {}\nNODATA_value -9999\n\
+                 1 1 1 1 1 1 1 1 1 1 1 2001\n1 1 1 1 1 1 1 1 1 1 1 2002",
+                cellsize
+            );
+            fs::write(&grid_path, grid_content)?;
+            let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+            let agents = vec![
+                Agent::new(0, 0, 0, AgentType::Adult, true),
+                Agent::new(1, 0, 1, AgentType::Elder, true),
+            ];
+            for agent in &agents {
+                grid.add_agent(agent.x, agent.y, agent.id);
+            }
+            let mut model = simulation_game::Model::new(grid, agents);
+            for step in 0..10 {
+                model.step(step, false, 0);
+            }
+            Ok((model.agents[0].x, model.agents[1].x))
+        };
+
+        // 26.6 m and 18.62 m walked in 10 s
+        assert_eq!(walk(5.0)?, (5, 3));
+        assert_eq!(walk(2.5)?, (10, 7));
+
+        clean_test_data();
+        Ok(())
     }
-    
+
     // Test simulation initialization with default config
     #[test]
     fn test_simulation_init_default() -> io::Result<()> {
@@ -271,15 +1354,15 @@ NODATA_value -9999
         let simulation = Simulation::new(&grid_path, &pop_path)?;
         
         // Check default configuration values
-        assert_eq!(simulation.config.tsunami_delay, 30 * 60);
-        assert_eq!(simulation.config.tsunami_speed_time, 28);
+        assert_eq!(simulation.config.tsunami_delay, 30.0 * 60.0);
+        assert_eq!(simulation.config.tsunami_speed_time, 28.0);
         assert_eq!(simulation.config.distribution_weights, [10, 20, 30, 15, 20]);
         assert_eq!(simulation.config.base_speed, 2.66);
         assert_eq!(simulation.config.agent_speed_multipliers, [0.8, 1.0, 1.0, 0.7]);
         
         // Check initial simulation state
-        assert_eq!(simulation.current_step, 0);
-        assert_eq!(simulation.is_tsunami, false);
+        assert_eq!(simulation.current_step(), 0);
+        assert!(!simulation.is_tsunami);
         assert_eq!(simulation.tsunami_index, 0);
         
         clean_test_data();
@@ -293,25 +1376,26 @@ NODATA_value -9999
         let pop_path = create_test_population("sim_init_custom")?;
         
         let config = SimulationConfig {
-            tsunami_delay: 600,
-            tsunami_speed_time: 10,
+            tsunami_delay: 600.0,
+            tsunami_speed_time: 10.0,
             distribution_weights: [15, 15, 40, 15, 15],
             base_speed: 3.0,
             agent_speed_multipliers: [0.7, 0.9, 1.0, 0.6],
             agent_type_weights: [10.0, 20.0, 50.0, 20.0],
-            data_collection_interval: 15,
+            data_collection_interval: 15.0,
+            ..SimulationConfig::default()
         };
         
         let simulation = Simulation::with_config(&grid_path, &pop_path, config.clone())?;
         
         // Check custom configuration values were applied
-        assert_eq!(simulation.config.tsunami_delay, 600);
-        assert_eq!(simulation.config.tsunami_speed_time, 10);
+        assert_eq!(simulation.config.tsunami_delay, 600.0);
+        assert_eq!(simulation.config.tsunami_speed_time, 10.0);
         assert_eq!(simulation.config.distribution_weights, [15, 15, 40, 15, 15]);
         assert_eq!(simulation.config.base_speed, 3.0);
         assert_eq!(simulation.config.agent_speed_multipliers, [0.7, 0.9, 1.0, 0.6]);
         assert_eq!(simulation.config.agent_type_weights, [10.0, 20.0, 50.0, 20.0]);
-        assert_eq!(simulation.config.data_collection_interval, 15);
+        assert_eq!(simulation.config.data_collection_interval, 15.0);
         
         clean_test_data();
         Ok(())
@@ -324,14 +1408,14 @@ NODATA_value -9999
         let pop_path = create_test_population("sim_builder")?;
         
         let simulation = Simulation::new(&grid_path, &pop_path)?
-            .with_tsunami_delay(500)
-            .with_tsunami_speed_time(15)
-            .with_data_collection_interval(10);
+            .with_tsunami_delay(500.0)
+            .with_tsunami_speed_time(15.0)
+            .with_data_collection_interval(10.0);
         
         // Check that builder methods properly updated the config
-        assert_eq!(simulation.config.tsunami_delay, 500);
-        assert_eq!(simulation.config.tsunami_speed_time, 15);
-        assert_eq!(simulation.config.data_collection_interval, 10);
+        assert_eq!(simulation.config.tsunami_delay, 500.0);
+        assert_eq!(simulation.config.tsunami_speed_time, 15.0);
+        assert_eq!(simulation.config.data_collection_interval, 10.0);
         
         clean_test_data();
         Ok(())
@@ -372,6 +1456,7 @@ NODATA_value -9999
             allow_diagonal: false,
             shelter_capacity: -1,
             path_algorithm: "bfs".to_string(),
+            ..GridConfig::default()
         };
         
         let (mut grid_bfs, _) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config_bfs)?;
@@ -383,17 +1468,19 @@ NODATA_value -9999
             allow_diagonal: false,
             shelter_capacity: -1,
             path_algorithm: "dijkstra".to_string(),
+            ..GridConfig::default()
         };
         
         let (mut grid_dijkstra, _) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config_dijkstra)?;
         grid_dijkstra.compute_distance_to_shelters();
         
-        // Test A* pathfinding (currently falls back to Dijkstra)
+        // Test A* configuration (distance field still comes from Dijkstra)
         let config_astar = GridConfig {
             blocked_penalty: 2,
             allow_diagonal: false,
             shelter_capacity: -1,
             path_algorithm: "a_star".to_string(),
+            ..GridConfig::default()
         };
         
         let (mut grid_astar, _) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config_astar)?;
@@ -415,6 +1502,78 @@ NODATA_value -9999
         Ok(())
     }
     
+    fn grid_agent_walks_plan(mut grid: grid::Grid, plan: &grid::PathPlan) {
+        let (x, y) = plan.path[0];
+        grid.add_agent(x, y, 0);
+        let mut model = simulation_game::Model::new(grid, vec![Agent::new(0, x, y, AgentType::Adult, true)]);
+        model.set_seed(1);
+        for step in 0..100 {
+            model.step(step, false, 0);
+            let agent = &model.agents[0];
+            assert!(plan.path.contains(&(agent.x, agent.y)));
+        }
+        assert_eq!(model.agent_paths.get(&0), None);
+        assert_eq!(model.shelter_arrivals.len(), 1);
+    }
+
+    // Test per-agent A* planning against the Dijkstra distance field
+    #[test]
+    fn test_astar_path_planning() -> io::Result<()> {
+        let grid_path = create_test_grid("astar_planning")?;
+
+        for allow_diagonal in [false, true] {
+            let config = GridConfig {
+                blocked_penalty: 2,
+                allow_diagonal,
+                shelter_capacity: -1,
+                path_algorithm: "a_star".to_string(),
+                ..GridConfig::default()
+            };
+            let (grid, _) = load_grid_from_ascii_with_config(&grid_path, config)?;
+
+            for &(x, y) in &[(1u32, 1u32), (8, 7), (3, 9), (0, 0)] {
+                let plan = grid.plan_path_astar(x, y).expect("shelter should be reachable");
+
+                // Path runs from the start cell to the shelter
+                assert_eq!(plan.path.first(), Some(&(x, y)));
+                assert_eq!(plan.path.last(), Some(&(4, 4)));
+                assert_eq!(plan.shelter_id, 1);
+                assert!(plan.nodes_expanded > 0);
+
+                // A* is optimal under the same cost model as the distance field
+                assert_eq!(Some(plan.cost), grid.distance_to_shelter[y as usize][x as usize]);
+
+                // Consecutive cells are neighbours and the length is in real-world units
+                for step in plan.path.windows(2) {
+                    let dx = step[0].0.abs_diff(step[1].0);
+                    let dy = step[0].1.abs_diff(step[1].1);
+                    assert!(dx <= 1 && dy <= 1 && dx + dy > 0);
+                    assert!(allow_diagonal || dx + dy == 1);
+                }
+                assert!(plan.length_m >= (plan.path.len() - 1) as f64 * grid.cellsize);
+
+                // Following the global field reaches the same shelter
+                let field_path = grid.trace_distance_field_path(x, y).unwrap();
+                assert_eq!(field_path.last(), Some(&(4, 4)));
+            }
+
+            // Agents walk their planned route to the shelter
+            let plan = grid.plan_path_astar(8, 7).unwrap();
+            grid_agent_walks_plan(grid, &plan);
+        }
+
+        // Unknown algorithms are rejected when the grid is loaded
+        let config = GridConfig {
+            path_algorithm: "astar".to_string(),
+            ..GridConfig::default()
+        };
+        let err = load_grid_from_ascii_with_config(&grid_path, config).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        clean_test_data();
+        Ok(())
+    }
+
     // Test road distance computation
     #[test]
     fn test_road_distance_calculation() -> io::Result<()> {
@@ -427,12 +1586,18 @@ NODATA_value -9999
             cellsize: 5.0,
             terrain: vec![vec![Terrain::Blocked; 5]; 5],
             shelters: Vec::new(),
+            shelter_entrances: ShelterEntrances::default(),
+            signs: Vec::new(),
             agents_in_cell: vec![vec![Vec::new(); 5]; 5],
             distance_to_shelter: vec![vec![None; 5]; 5],
+            shelter_distance_fields: HashMap::new(),
+            flooded: Vec::new(),
+            failed_shelters: HashSet::new(),
             shelter_agents: HashMap::new(),
             distance_to_road: vec![vec![None; 5]; 5],
             population: vec![vec![0; 5]; 5],
             tsunami_data: Vec::new(),
+            tsunami_source: TsunamiSourceHandle::default(),
             nrow: 5,
             ncol: 5,
             config: GridConfig::default(),
@@ -458,7 +1623,7 @@ NODATA_value -9999
         }
         
         // Check that at least one blocked cell has a distance > 0
-        assert!(grid.distance_to_road.iter().flatten().any(|&d| d.map_or(false, |v| v > 0)));
+        assert!(grid.distance_to_road.iter().flatten().any(|&d| d.is_some_and(|v| v > 0)));
         
         Ok(())
     }
@@ -475,12 +1640,18 @@ NODATA_value -9999
             cellsize: 5.0,
             terrain: vec![vec![Terrain::Blocked; 5]; 5],
             shelters: vec![(2, 2, 1)], // Add one shelter with ID 1
+            shelter_entrances: ShelterEntrances::default(),
+            signs: Vec::new(),
             agents_in_cell: vec![vec![Vec::new(); 5]; 5],
             distance_to_shelter: vec![vec![None; 5]; 5],
+            shelter_distance_fields: HashMap::new(),
+            flooded: Vec::new(),
+            failed_shelters: HashSet::new(),
             shelter_agents: HashMap::new(),
             distance_to_road: vec![vec![None; 5]; 5],
             population: vec![vec![0; 5]; 5],
             tsunami_data: Vec::new(),
+            tsunami_source: TsunamiSourceHandle::default(),
             nrow: 5,
             ncol: 5,
             config: GridConfig::default(),
@@ -497,12 +1668,7 @@ NODATA_value -9999
         agents.push(agent);
         
         // Create a model
-        let mut model = simulation_game::Model {
-            grid,
-            agents,
-            dead_agents: 0,
-            dead_agent_types: Vec::new(),
-        };
+        let mut model = simulation_game::Model::new(grid, agents);
         
         // Add agent to shelter
         let shelter_id = 1;
@@ -517,6 +1683,359 @@ NODATA_value -9999
         Ok(())
     }
     
+    // Test shelter capacity limits and rerouting to the next shelter with space
+    #[test]
+    fn test_shelter_capacity_and_rerouting() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+
+        let grid_path = test_dir.join("shelter_capacity_grid.asc");
+        let grid_content = "ncols 7
+nrows 3
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+0 0 0 0 0 0 0
+2001 3 3 1 1 1 2002
+0 0 0 0 0 0 0";
+        fs::write(&grid_path, grid_content)?;
+
+        let mut shelter_capacities = HashMap::new();
+        shelter_capacities.insert(1, 1);
+        let config = GridConfig {
+            shelter_capacity: 5,
+            shelter_capacities,
+            ..GridConfig::default()
+        };
+
+        let (mut grid, agents) = load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config)?;
+        assert_eq!(grid.shelter_capacity(1), Some(1));
+        assert_eq!(grid.shelter_capacity(2), Some(5));
+        assert_eq!(grid.distance_to_shelter[1][2], Some(2));
+
+        for agent in &agents {
+            grid.add_agent(agent.x, agent.y, agent.id);
+        }
+        let mut model = simulation_game::Model::new(grid, agents);
+
+        for step in 0..10 {
+            model.step(step, false, 0);
+        }
+
+        // The near shelter only admits one agent, the other walks on to shelter 2
+        assert_eq!(model.grid.shelter_occupancy(1), 1);
+        assert_eq!(model.grid.shelter_occupancy(2), 1);
+        assert!(!model.grid.shelter_has_space(1));
+
+        // The full shelter is no longer a destination in the distance field
+        assert_eq!(model.grid.distance_to_shelter[1][0], None);
+        assert_eq!(model.grid.distance_to_shelter[1][2], Some(4));
+
+        // Agents are rejected at the door once the shelter is full
+        assert!(!model.grid.add_to_shelter(1, 99, AgentType::Adult));
+        assert_eq!(model.grid.shelter_occupancy(1), 1);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test per-shelter distance fields and shelter-choice policies
+    #[test]
+    fn test_shelter_choice_policies() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+
+        let grid_path = test_dir.join("shelter_choice_grid.asc");
+        let grid_content = "ncols 7
+nrows 3
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+0 0 0 0 0 0 0
+2001 3 3 1 1 1 2002
+0 0 0 0 0 0 0";
+        fs::write(&grid_path, grid_content)?;
+
+        let mut shelter_capacities = HashMap::new();
+        shelter_capacities.insert(1, 1);
+        let config = GridConfig {
+            shelter_capacities,
+            ..GridConfig::default()
+        };
+
+        let load_model = || -> io::Result<simulation_game::Model> {
+            let (mut grid, agents) =
+                load_grid_from_ascii_with_config(&grid_path.to_string_lossy(), config.clone())?;
+            for agent in &agents {
+                grid.add_agent(agent.x, agent.y, agent.id);
+            }
+            Ok(simulation_game::Model::new(grid, agents))
+        };
+
+        // Nearest keeps following the shared field and computes no extra fields
+        let mut model = load_model()?;
+        model.set_shelter_choice(ShelterChoicePolicy::Nearest);
+        assert!(model.grid.shelter_distance_fields.is_empty());
+        assert!(model.agents.iter().all(|a| a.target_shelter.is_none()));
+
+        // One field per shelter; each routes to its own shelter only
+        let mut model = load_model()?;
+        model.set_shelter_choice(ShelterChoicePolicy::CapacityAware);
+        assert_eq!(model.grid.shelter_distance_fields.len(), 2);
+        assert_eq!(model.grid.distance_to_shelter_id(1, 2, 1), Some(2));
+        assert_eq!(model.grid.distance_to_shelter_id(2, 2, 1), Some(4));
+        assert_eq!(model.grid.distance_to_shelter_id(2, 0, 1), None);
+
+        // Shelter 1 holds one agent, so the second is sent to shelter 2 up front
+        let mut targets: Vec<Option<u32>> = model.agents.iter().map(|a| a.target_shelter).collect();
+        targets.sort();
+        assert_eq!(targets, vec![Some(1), Some(2)]);
+
+        // Zone assignment sends everyone in zone 7 to the far shelter
+        let mut assignments = HashMap::new();
+        assignments.insert(7, 2);
+        let mut model = load_model()?;
+        model.set_shelter_choice(ShelterChoicePolicy::AssignedByZone {
+            zones: vec![vec![7; 7]; 3],
+            assignments,
+        });
+        assert!(model.agents.iter().all(|a| a.target_shelter == Some(2)));
+
+        for step in 0..30 {
+            model.step(step, false, 0);
+        }
+        assert_eq!(model.grid.shelter_occupancy(1), 0);
+        assert_eq!(model.grid.shelter_occupancy(2), 2);
+
+        // Agents keep the zone they started in after moving into another one
+        let mut assignments = HashMap::new();
+        assignments.insert(7, 2);
+        assignments.insert(8, 1);
+        let zones = vec![vec![7, 7, 7, 8, 8, 8, 8]; 3];
+        let policy = ShelterChoicePolicy::AssignedByZone { zones, assignments };
+        let model = load_model()?;
+        let mut rng = stream_rng(0, RngStream::ShelterChoice);
+        let pending = HashMap::new();
+        let mut grid = model.grid.clone();
+        grid.compute_shelter_distance_fields();
+        assert_eq!(policy.choose(&grid, 4, 1, (1, 1), &pending, &mut rng), Some(2));
+        assert_eq!(policy.choose(&grid, 4, 1, (4, 1), &pending, &mut rng), Some(1));
+
+        // Zero attractiveness rules a shelter out of the weighted draw
+        let mut attractiveness = HashMap::new();
+        attractiveness.insert(1, 0.0);
+        let mut model = load_model()?;
+        model.set_shelter_choice(ShelterChoicePolicy::RandomWeighted { attractiveness });
+        assert!(model.agents.iter().all(|a| a.target_shelter == Some(2)));
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test fragility curve probabilities and outcomes
+    #[test]
+    fn test_fragility_curves() {
+        let lognormal = FragilityCurve::Lognormal { median: 1.0, beta: 0.5 };
+        assert_eq!(lognormal.probability(0.0), 0.0);
+        assert!((lognormal.probability(1.0) - 0.5).abs() < 1e-6);
+        assert!(lognormal.probability(0.5) < 0.1);
+        assert!(lognormal.probability(3.0) > 0.95);
+
+        assert_eq!(FragilityCurve::Threshold(0.5).probability(0.49), 0.0);
+        assert_eq!(FragilityCurve::Threshold(0.5).probability(0.5), 1.0);
+        assert_eq!(FragilityCurve::AnyDepth.probability(0.01), 1.0);
+        assert_eq!(FragilityCurve::Never.probability(10.0), 0.0);
+
+        let config = FragilityConfig::step_thresholds();
+        assert_eq!(config.outcome(AgentType::Child, 0.7, 0.99), HazardOutcome::Dead);
+        assert_eq!(config.outcome(AgentType::Adult, 0.7, 0.0), HazardOutcome::Slowed);
+        assert_eq!(config.outcome(AgentType::Elder, 0.2, 0.0), HazardOutcome::Unharmed);
+        assert_eq!(config.outcome(AgentType::Custom(1.2), 1.0, 0.5), HazardOutcome::Dead);
+    }
+
+    // Test that tsunami depth, not mere presence of water, decides casualties
+    #[test]
+    fn test_depth_dependent_mortality() {
+        let mut grid = Grid {
+            width: 5,
+            height: 5,
+            xllcorner: 100.0,
+            yllcorner: 200.0,
+            cellsize: 5.0,
+            terrain: vec![vec![Terrain::Road; 5]; 5],
+            shelters: Vec::new(),
+            shelter_entrances: ShelterEntrances::default(),
+            signs: Vec::new(),
+            agents_in_cell: vec![vec![Vec::new(); 5]; 5],
+            distance_to_shelter: vec![vec![None; 5]; 5],
+            shelter_distance_fields: HashMap::new(),
+            flooded: Vec::new(),
+            failed_shelters: HashSet::new(),
+            shelter_agents: HashMap::new(),
+            distance_to_road: vec![vec![Some(0); 5]; 5],
+            population: vec![vec![0; 5]; 5],
+            tsunami_data: vec![vec![vec![0.0; 5]; 5]],
+            tsunami_source: TsunamiSourceHandle::default(),
+            nrow: 5,
+            ncol: 5,
+            config: GridConfig::default(),
+        };
+        // 0.8 m of water along the top row, a 0.4 m wet fringe below it
+        grid.tsunami_data[0][0] = vec![0.8; 5];
+        grid.tsunami_data[0][1] = vec![0.4; 5];
+
+        let agents = vec![
+            Agent::new(0, 0, 0, AgentType::Child, true),
+            Agent::new(1, 2, 0, AgentType::Adult, true),
+            Agent::new(2, 4, 1, AgentType::Elder, true),
+            Agent::new(3, 2, 4, AgentType::Child, true),
+        ];
+        for agent in &agents {
+            grid.add_agent(agent.x, agent.y, agent.id);
+        }
+
+        let mut model = simulation_game::Model::new(grid, agents);
+        model.fragility = FragilityConfig::step_thresholds();
+        model.step(0, true, 0);
+
+        // Only the child in 0.8 m of water dies; depth is no longer truncated
+        assert_eq!(model.dead_agents, 1);
+        assert_eq!(model.dead_agent_types, vec![AgentType::Child]);
+        assert_eq!(model.agents.len(), 3);
+        // Vulnerability is only drawn for agents that were caught in water
+        for agent in &model.agents {
+            assert_eq!(agent.fragility_quantile.is_some(), agent.id != 3);
+        }
+
+        // With the default curves any water is fatal
+        let mut grid = model.grid.clone();
+        grid.agents_in_cell = vec![vec![Vec::new(); 5]; 5];
+        let agents = vec![Agent::new(0, 1, 1, AgentType::Adult, true)];
+        grid.add_agent(1, 1, 0);
+        let mut model = simulation_game::Model::new(grid, agents);
+        model.step(0, true, 0);
+        assert_eq!(model.dead_agents, 1);
+    }
+
+    // Test depth sampled between tsunami frames
+    #[test]
+    fn test_tsunami_frame_interpolation() {
+        let mut grid = Grid {
+            width: 5,
+            height: 5,
+            xllcorner: 100.0,
+            yllcorner: 200.0,
+            cellsize: 5.0,
+            terrain: vec![vec![Terrain::Road; 5]; 5],
+            shelters: Vec::new(),
+            shelter_entrances: ShelterEntrances::default(),
+            signs: Vec::new(),
+            agents_in_cell: vec![vec![Vec::new(); 5]; 5],
+            distance_to_shelter: vec![vec![None; 5]; 5],
+            shelter_distance_fields: HashMap::new(),
+            flooded: Vec::new(),
+            failed_shelters: HashSet::new(),
+            shelter_agents: HashMap::new(),
+            distance_to_road: vec![vec![Some(0); 5]; 5],
+            population: vec![vec![0; 5]; 5],
+            tsunami_data: vec![vec![vec![0.0; 5]; 5]; 3],
+            tsunami_source: TsunamiSourceHandle::default(),
+            nrow: 5,
+            ncol: 5,
+            config: GridConfig::default(),
+        };
+        // The wave reaches 2 m at (0, 0) in frame 1 and recedes in frame 2
+        grid.tsunami_data[1][0][0] = 2.0;
+
+        assert_eq!(grid.tsunami_depth_at(0.25, 0, 0, FrameInterpolation::Discrete, None), 0.0);
+        assert_eq!(grid.tsunami_depth_at(0.25, 0, 0, FrameInterpolation::Linear, None), 0.5);
+        assert_eq!(grid.tsunami_depth_at(1.5, 0, 0, FrameInterpolation::Linear, None), 1.0);
+        // The envelope keeps the peak without reading earlier frames again
+        let mut envelope = MaxDepthEnvelope::default();
+        envelope.advance(grid.tsunami(), 0).unwrap();
+        assert_eq!(grid.tsunami_depth_at(0.25, 0, 0, FrameInterpolation::MaxSoFar, Some(&envelope)), 0.5);
+        envelope.advance(grid.tsunami(), 1).unwrap();
+        assert_eq!(grid.tsunami_depth_at(1.5, 0, 0, FrameInterpolation::MaxSoFar, Some(&envelope)), 2.0);
+        assert_eq!(grid.tsunami_depth_at(1.5, 0, 0, FrameInterpolation::MaxSoFar, None), 1.0);
+        // Past the last frame its depth holds
+        envelope.advance(grid.tsunami(), 7).unwrap();
+        assert_eq!(grid.tsunami_depth_at(7.0, 0, 0, FrameInterpolation::MaxSoFar, Some(&envelope)), 2.0);
+        assert_eq!(grid.tsunami_depth_at(7.0, 0, 0, FrameInterpolation::Linear, None), 0.0);
+
+        // Adults die at 1 m: a quarter of the way to the peak frame is survivable,
+        // three quarters of the way is not
+        let mut agent = Agent::new(0, 0, 0, AgentType::Adult, true);
+        agent.speed = 0.0;
+        grid.add_agent(0, 0, 0);
+        let mut model = simulation_game::Model::new(grid, vec![agent]);
+        model.fragility = FragilityConfig::step_thresholds();
+        model.tsunami_interpolation = FrameInterpolation::Linear;
+        model.step_at(0, true, 0.25);
+        assert_eq!(model.dead_agents, 0);
+        model.step_at(1, true, 0.75);
+        assert_eq!(model.dead_agents, 1);
+    }
+
+    // Test departure-delay distributions and agents waiting out their milling time
+    #[test]
+    fn test_departure_delays() -> io::Result<()> {
+        let rayleigh = DepartureDistribution::Rayleigh { sigma: 10.0 };
+        assert_eq!(rayleigh.quantile(0.0), 0.0);
+        // Median of a Rayleigh distribution is sigma * sqrt(2 ln 2)
+        assert!((rayleigh.quantile(0.5) - 10.0 * (2.0 * 2f64.ln()).sqrt()).abs() < 1e-9);
+
+        let empirical = DepartureDistribution::Empirical {
+            points: vec![(0.0, 0.0), (10.0, 0.5), (30.0, 1.0)],
+        };
+        assert_eq!(empirical.quantile(0.25), 5.0);
+        assert_eq!(empirical.quantile(0.75), 20.0);
+        assert_eq!(empirical.quantile(1.0), 30.0);
+
+        let uniform = DepartureDistribution::Uniform { min: 2.0, max: 4.0 };
+        let mut rng = stream_rng(3, RngStream::Departure);
+        assert!((0..100).map(|_| uniform.sample(&mut rng)).all(|d| (2.0..=4.0).contains(&d)));
+
+        let mut departure = DepartureConfig::default();
+        departure.milling[3] = DepartureDistribution::Fixed(25.0);
+        assert_eq!(departure.departure_time(AgentType::Adult, 10.0, &mut rng), 10.0);
+        assert_eq!(departure.departure_time(AgentType::Elder, 10.0, &mut rng), 35.0);
+
+        // Agents do not move before their departure step
+        let grid_path = create_test_grid("departure")?;
+        let (mut grid, _) = load_grid_from_ascii(&grid_path)?;
+        let agents = vec![
+            Agent::new(0, 2, 1, AgentType::Adult, true),
+            Agent::new(1, 7, 1, AgentType::Elder, true),
+        ];
+        for agent in &agents {
+            grid.add_agent(agent.x, agent.y, agent.id);
+        }
+        let mut model = simulation_game::Model::new(grid, agents);
+        // Departure times are converted to steps of 5 s
+        model.clock.dt = 5.0;
+        model.assign_departure_times(&departure, 10.0);
+        assert_eq!(model.agents[0].departure_step, 2);
+        assert_eq!(model.agents[1].departure_step, 7);
+
+        for step in 0..2 {
+            model.step(step, false, 0);
+        }
+        assert_eq!((model.agents[0].x, model.agents[0].y), (2, 1));
+        model.step(2, false, 0);
+        assert_ne!((model.agents[0].x, model.agents[0].y), (2, 1));
+        for step in 3..7 {
+            model.step(step, false, 0);
+        }
+        assert_eq!((model.agents[1].x, model.agents[1].y), (7, 1));
+        model.step(7, false, 0);
+        assert_ne!((model.agents[1].x, model.agents[1].y), (7, 1));
+
+        clean_test_data();
+        Ok(())
+    }
+
     // Test agent data collection
     #[test]
     fn test_agent_data_collection() -> io::Result<()> {
@@ -529,32 +2048,34 @@ NODATA_value -9999
             cellsize: 5.0,
             terrain: vec![vec![Terrain::Blocked; 5]; 5],
             shelters: Vec::new(),
+            shelter_entrances: ShelterEntrances::default(),
+            signs: Vec::new(),
             agents_in_cell: vec![vec![Vec::new(); 5]; 5],
             distance_to_shelter: vec![vec![None; 5]; 5],
+            shelter_distance_fields: HashMap::new(),
+            flooded: Vec::new(),
+            failed_shelters: HashSet::new(),
             shelter_agents: HashMap::new(),
             distance_to_road: vec![vec![None; 5]; 5],
             population: vec![vec![0; 5]; 5],
             tsunami_data: Vec::new(),
+            tsunami_source: TsunamiSourceHandle::default(),
             nrow: 5,
             ncol: 5,
             config: GridConfig::default(),
         };
         
         // Create some agents for testing
-        let mut agents = Vec::new();
-        agents.push(Agent::new(0, 1, 1, AgentType::Adult, true));
-        agents.push(Agent::new(1, 2, 2, AgentType::Child, true));
+        let agents = vec![
+            Agent::new(0, 1, 1, AgentType::Adult, true),
+            Agent::new(1, 2, 2, AgentType::Child, true),
+        ];
         
         // Create a model with our test grid and agents
-        let model = simulation_game::Model {
-            grid: grid.clone(),
-            agents,
-            dead_agents: 0,
-            dead_agent_types: Vec::new(),
-        };
+        let model = simulation_game::Model::new(grid, agents);
         
         // Create a collector and collect data
-        let mut collector = AgentDataCollector::new(grid);
+        let mut collector = AgentDataCollector::new();
         
         // Collect data for multiple steps
         collector.collect_step(&model, 0);
@@ -580,29 +2101,29 @@ NODATA_value -9999
             cellsize: 5.0,
             terrain: vec![vec![Terrain::Blocked; 5]; 5],
             shelters: Vec::new(),
+            shelter_entrances: ShelterEntrances::default(),
+            signs: Vec::new(),
             agents_in_cell: vec![vec![Vec::new(); 5]; 5],
             distance_to_shelter: vec![vec![None; 5]; 5],
+            shelter_distance_fields: HashMap::new(),
+            flooded: Vec::new(),
+            failed_shelters: HashSet::new(),
             shelter_agents: HashMap::new(),
             distance_to_road: vec![vec![None; 5]; 5],
             population: vec![vec![0; 5]; 5],
             tsunami_data: Vec::new(),
+            tsunami_source: TsunamiSourceHandle::default(),
             nrow: 5,
             ncol: 5,
             config: GridConfig::default(),
         };
         
-        let mut collector = AgentDataCollector::new(grid.clone());
+        let mut collector = AgentDataCollector::new();
         
         // Create a dummy model with test agents
-        let mut agents = Vec::new();
-        agents.push(Agent::new(0, 1, 1, AgentType::Adult, true));
+        let agents = vec![Agent::new(0, 1, 1, AgentType::Adult, true)];
         
-        let model = simulation_game::Model {
-            grid: grid.clone(),
-            agents,
-            dead_agents: 0,
-            dead_agent_types: Vec::new(),
-        };
+        let model = simulation_game::Model::new(grid.clone(), agents);
         
         // Manually add some agent data
         collector.collect_step(&model, 0);
@@ -634,8 +2155,8 @@ NODATA_value -9999
         
         // Create simulation with short tsunami delay
         let mut simulation = Simulation::new(&grid_path, &pop_path)?
-            .with_tsunami_delay(5)
-            .with_tsunami_speed_time(2);
+            .with_tsunami_delay(5.0)
+            .with_tsunami_speed_time(2.0);
         
         // Run until after tsunami starts
         simulation.run(Some(10))?;
@@ -648,6 +2169,304 @@ NODATA_value -9999
         Ok(())
     }
     
+    // Helper function to write tsunami frames where frame `i` has depth `i` everywhere
+    fn create_test_tsunami(test_name: &str, prefix: &str, count: u32, header: &str) -> io::Result<String> {
+        let tsunami_dir = Path::new("test_data").join(format!("{}_tsunami", test_name));
+        fs::create_dir_all(&tsunami_dir)?;
+
+        for i in 0..count {
+            let row = vec![i.to_string(); 10].join(" ");
+            let rows = vec![row; 10].join("\n");
+            fs::write(
+                tsunami_dir.join(format!("{}{}.asc", prefix, i)),
+                format!("{}\n{}", header, rows),
+            )?;
+        }
+        // Files not matching the pattern are ignored
+        fs::write(tsunami_dir.join("readme.txt"), "not a frame")?;
+
+        Ok(tsunami_dir.to_string_lossy().to_string())
+    }
+
+    // Test loading, ordering, subsampling and validation of tsunami frames
+    #[test]
+    fn test_tsunami_series_loading() -> io::Result<()> {
+        let grid_path = create_test_grid("tsunami_series")?;
+        let pop_path = create_test_population("tsunami_series")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("tsunami_series", "wave_z_", 12, header)?;
+        let (grid, _) = load_grid_from_ascii(&grid_path)?;
+
+        // Numeric ordering (wave_z_10 after wave_z_9) with every 4th frame kept
+        let config = TsunamiSeriesConfig::default()
+            .with_file_pattern(r"wave_z_(\d+)\.asc$")
+            .with_frame_stride(4)
+            .with_frame_time_step(7.0);
+        let series = TsunamiSeries::load(&tsunami_dir, &grid, &config)?;
+        assert_eq!(series.frame_indices, vec![0, 4, 8]);
+        assert_eq!(series.len(), 3);
+        assert_eq!(series.frames[2][5][5], 8.0);
+        assert_eq!(series.frame_interval, 28.0);
+
+        // No matching files is an error
+        let config = TsunamiSeriesConfig::default().with_file_pattern(r"other_(\d+)\.asc$");
+        assert!(TsunamiSeries::load(&tsunami_dir, &grid, &config).is_err());
+
+        // Frames for a different raster are rejected unless validation is off
+        let shifted = "ncols 10\nnrows 10\nxllcorner 150.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let shifted_dir = create_test_tsunami("tsunami_series_shifted", "wave_", 2, shifted)?;
+        let err = TsunamiSeries::load(&shifted_dir, &grid, &TsunamiSeriesConfig::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let config = TsunamiSeriesConfig {
+            validate_header: false,
+            ..TsunamiSeriesConfig::default()
+        };
+        assert_eq!(TsunamiSeries::load(&shifted_dir, &grid, &config)?.len(), 2);
+
+        // Simulation loads the frames from the configured path
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir.clone()),
+            ..SimulationConfig::default()
+        };
+        let simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+        assert_eq!(simulation.model.grid.tsunami_data.len(), 12);
+
+        // With a stride the kept frames are further apart and must be played
+        // back at that interval, not at the default 28 s
+        let strided = TsunamiSeriesConfig::default().with_frame_stride(4).with_frame_time_step(1.0);
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir.clone()),
+            tsunami_series: strided.clone(),
+            ..SimulationConfig::default()
+        };
+        let result = Simulation::with_config(&grid_path, &pop_path, config.clone());
+        assert!(result.is_err_and(|err| err.kind() == io::ErrorKind::InvalidInput));
+        let config = SimulationConfig {
+            tsunami_speed_time: 4.0,
+            tsunami_delay: 0.0,
+            ..config
+        };
+        let mut simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+        assert_eq!(simulation.model.grid.tsunami_data.len(), 3);
+        for _ in 0..5 {
+            simulation.step();
+        }
+        // Kept frame 1 is file 4, reached 4 s (one stride of 1 s frames) in
+        assert_eq!(simulation.tsunami_index, 1);
+        assert_eq!(simulation.model.grid.tsunami_depth(simulation.tsunami_index, 5, 5), 4.0);
+        assert_eq!(simulation.compute_hazard_maps()?.frame_interval, 4.0);
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test arrival-time, maximum-depth and wet-duration maps derived from the frames
+    #[test]
+    fn test_hazard_maps() -> io::Result<()> {
+        let grid_path = create_test_grid("hazard_maps")?;
+        let pop_path = create_test_population("hazard_maps")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("hazard_maps", "wave_", 4, header)?;
+
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir),
+            tsunami_delay: 60.0,
+            hazard_wet_depth: 1.5,
+            ..SimulationConfig::default()
+        };
+        let mut simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+        // (0, 0) stays dry and (1, 0) is hit by a 5 m wave in frame 1
+        for frame in simulation.model.grid.tsunami_data.iter_mut() {
+            frame[0][0] = 0.0;
+        }
+        simulation.model.grid.tsunami_data[1][0][1] = 5.0;
+        assert_eq!(simulation.time_until_flooded(2, 2), None);
+
+        let maps = simulation.compute_hazard_maps()?.clone();
+        assert_eq!(maps.frame_interval, 28.0);
+        assert_eq!(maps.arrival_time(0, 0), None);
+        assert_eq!(maps.max_depth(0, 0), 0.0);
+        assert_eq!(maps.wet_duration(0, 0), 0.0);
+        assert_eq!(maps.arrival_time(1, 0), Some(28.0));
+        assert_eq!(maps.max_depth(1, 0), 5.0);
+        assert_eq!(maps.wet_duration(1, 0), 84.0);
+        assert_eq!(maps.arrival_time(2, 2), Some(56.0));
+        assert_eq!(maps.max_depth(2, 2), 3.0);
+        assert_eq!(maps.wet_duration(2, 2), 56.0);
+
+        // The first frame arrives after the tsunami delay
+        assert_eq!(simulation.time_until_flooded(2, 2), Some(116.0));
+        assert_eq!(simulation.time_until_flooded(0, 0), None);
+        assert_eq!(maps.time_until_flooded(2, 2, 100.0), Some(0.0));
+        // The delay counts in whole steps, as it does when the frames are played
+        simulation.config.tsunami_delay = 59.6;
+        assert_eq!(simulation.time_until_flooded(2, 2), Some(116.0));
+        simulation.config.tsunami_delay = 60.0;
+
+        // ... and the simulation reaches frame 2 when (2, 2) floods
+        simulation.run(Some(115))?;
+        assert_eq!(simulation.time_until_flooded(2, 2), Some(1.0));
+        simulation.step();
+        assert_eq!(simulation.time_until_flooded(2, 2), Some(0.0));
+        assert_eq!(simulation.tsunami_frame_position(), 2.0);
+        simulation.step();
+        assert_eq!(simulation.tsunami_index, 2);
+
+        // Written as ASCII grids on the grid's raster, NODATA where it never floods
+        let output_dir = Path::new("test_data").join("hazard_maps_output");
+        maps.write_ascii(&output_dir, &simulation.model.grid)?;
+        let content = fs::read_to_string(output_dir.join("arrival_time.asc"))?;
+        let mut lines = content.lines().map(|line| Ok(line.to_string()));
+        let header = tsunami::AsciiGridHeader::read(&mut lines)?;
+        assert!(header.validate_against(&simulation.model.grid).is_ok());
+        assert_eq!(header.nodata_value, Some(tsunami::HAZARD_NODATA));
+        let first_row: Vec<f64> = lines
+            .next()
+            .unwrap()?
+            .split_whitespace()
+            .map(|token| token.parse().unwrap())
+            .collect();
+        assert_eq!(&first_row[..3], &[-9999.0, 28.0, 56.0]);
+        let max_depth = tsunami::read_tsunami_frame(&output_dir.join("max_depth.asc"), 10, 10)?;
+        assert_eq!(max_depth, maps.max_depth);
+        assert!(output_dir.join("wet_duration.asc").exists());
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test tsunami frames generated from a shoreline, run-up and propagation speed
+    #[test]
+    fn test_synthetic_tsunami() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("synthetic_grid.asc");
+        let header = "ncols 10\nnrows 1\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999\n";
+        fs::write(&grid_path, format!("{}1 1 1 0 1 1 1 1 1 2001", header))?;
+        let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+
+        // 1 s per road cell, twice that through the blocked cell, 0.1 m lost per metre inland
+        let mut synthetic = SyntheticTsunami::new(Shoreline::Edge(GridEdge::West))
+            .with_run_up(5.0)
+            .with_attenuation(0.1)
+            .with_propagation_speed(5.0)
+            .with_frames(12, 1.0);
+        synthetic.rise_time = 0.0;
+        let frames = synthetic.frames(&grid);
+        assert_eq!(frames.len(), 12);
+        assert_eq!(frames[0][0][..2], [5.0, 0.0]);
+        assert_eq!(frames[3][0][2], 4.0);
+        assert_eq!(frames[3][0][3], 0.0);
+        assert_eq!(frames[4][0][3], 3.0);
+        // The wave dies out before reaching the shelter 45 m inland
+        assert!(frames.iter().all(|frame| frame[0][9] == 0.0));
+
+        let maps = tsunami::HazardMaps::compute(&frames, 1.0, 0.0)?;
+        assert_eq!(maps.arrival_time(4, 0), Some(5.0));
+        assert_eq!(maps.max_depth(4, 0), 2.5);
+
+        // The water rises over the rise time and drains after the inundation duration
+        synthetic.rise_time = 2.0;
+        synthetic.inundation_duration = Some(4.0);
+        let frames = synthetic.frames(&grid);
+        let depths: Vec<f32> = frames.iter().map(|frame| frame[0][0]).collect();
+        assert_eq!(depths[..8], [0.0, 2.5, 5.0, 5.0, 5.0, 2.5, 0.0, 0.0]);
+
+        // Shorelines given as a line or as cells
+        let line = SyntheticTsunami::new(Shoreline::Line { from: (0.5, 0.5), to: (2.5, 0.5) });
+        assert_eq!(line.shoreline_cells(&grid), vec![(0, 0), (1, 0), (2, 0)]);
+        let cells = SyntheticTsunami::new(Shoreline::Cells(vec![(9, 0), (3, 7)]));
+        assert_eq!(cells.shoreline_cells(&grid), vec![(9, 0)]);
+
+        // Written frames read back as a tsunami series
+        synthetic.inundation_duration = None;
+        let frame_dir = test_dir.join("synthetic_tsunami");
+        synthetic.write_frames(&frame_dir, &grid)?;
+        let series = TsunamiSeries::load(&frame_dir.to_string_lossy(), &grid, &TsunamiSeriesConfig::default())?;
+        assert_eq!(series.frames, synthetic.frames(&grid));
+
+        assert_eq!(synthetic.load_into(&mut grid), 12);
+        assert_eq!(grid.tsunami_frame_count(), 12);
+
+        // Simulations generate the frames when no tsunami directory is given
+        let pop_path = create_test_population("synthetic")?;
+        let grid_path = create_test_grid("synthetic")?;
+        let config = SimulationConfig {
+            synthetic_tsunami: Some(SyntheticTsunami::default().with_frames(5, 28.0)),
+            ..SimulationConfig::default()
+        };
+        let simulation = Simulation::with_config(&grid_path, &pop_path, config)?;
+        assert_eq!(simulation.model.grid.tsunami_data.len(), 5);
+        assert!(simulation.model.grid.tsunami_data[4][0][0] > 0.0);
+
+        // Frames generated at another interval than they are played back at are refused
+        let config = SimulationConfig {
+            synthetic_tsunami: Some(SyntheticTsunami::default().with_frames(5, 10.0)),
+            ..SimulationConfig::default()
+        };
+        let result = Simulation::with_config(&grid_path, &pop_path, config);
+        assert!(result.is_err_and(|err| err.kind() == io::ErrorKind::InvalidInput));
+
+        clean_test_data();
+        Ok(())
+    }
+
+    // Test frames streamed from disk through the LRU and binary cache
+    #[test]
+    fn test_streaming_tsunami_source() -> io::Result<()> {
+        let grid_path = create_test_grid("tsunami_stream")?;
+        let pop_path = create_test_population("tsunami_stream")?;
+        let header = "ncols 10\nnrows 10\nxllcorner 100.0\nyllcorner 200.0\ncellsize 5.0\nNODATA_value -9999";
+        let tsunami_dir = create_test_tsunami("tsunami_stream", "wave_", 6, header)?;
+        let (mut grid, _) = load_grid_from_ascii(&grid_path)?;
+
+        let config = TsunamiSeriesConfig::default().with_streaming(2);
+        let source = StreamingTsunami::open(&tsunami_dir, &grid, &config)?;
+        assert_eq!(source.frame_count(), 6);
+        assert_eq!(source.frame_indices(), vec![0, 1, 2, 3, 4, 5]);
+        assert!(source.frame(6).is_err());
+
+        // Frames match the in-memory loader and are cached in binary on first read
+        let series = TsunamiSeries::load(&tsunami_dir, &grid, &TsunamiSeriesConfig::default())?;
+        for index in 0..6 {
+            assert_eq!(*source.frame(index)?, series.frames[index]);
+            assert!(source.cache_path(index).exists());
+        }
+
+        // Later reads come from the binary cache rather than the ASCII file
+        let mut cached = fs::read(source.cache_path(3))?;
+        let first_value = 12..16;
+        cached[first_value].copy_from_slice(&42.0f32.to_le_bytes());
+        fs::write(source.cache_path(3), cached)?;
+        let reopened = StreamingTsunami::open(&tsunami_dir, &grid, &config)?;
+        assert_eq!(reopened.frame(3)?.depth(0, 0), 42.0);
+        assert_eq!(reopened.frame(3)?.depth(1, 0), 3.0);
+
+        // The grid reads through an attached source
+        let frame_count = tsunami::load_into_grid(&tsunami_dir, &mut grid, &config)?;
+        assert_eq!(frame_count, 6);
+        assert!(grid.tsunami_data.is_empty());
+        assert_eq!(grid.tsunami_frame_count(), 6);
+        assert_eq!(grid.tsunami_depth(5, 2, 2), 5.0);
+        assert_eq!(grid.tsunami_depth_at(4.5, 2, 2, FrameInterpolation::Linear, None), 4.5);
+
+        // A streamed simulation runs to the end of the frames
+        let config = SimulationConfig {
+            tsunami_data_path: Some(tsunami_dir.clone()),
+            tsunami_series: TsunamiSeriesConfig::default().with_streaming(1),
+            tsunami_interpolation: FrameInterpolation::MaxSoFar,
+            ..SimulationConfig::default()
+        };
+        let mut simulation = Simulation::with_config(&grid_path, &pop_path, config)?
+            .with_tsunami_delay(0.0)
+            .with_tsunami_speed_time(1.0);
+        simulation.run(Some(20))?;
+        assert_eq!(simulation.tsunami_index, 6);
+
+        clean_test_data();
+        Ok(())
+    }
+
     // Test agent movement on different terrain types
     #[test]
     fn test_terrain_movement_costs() -> io::Result<()> {
@@ -676,6 +2495,7 @@ NODATA_value -9999
             allow_diagonal: true,
             shelter_capacity: -1,
             path_algorithm: "dijkstra".to_string(),
+            ..GridConfig::default()
         };
         
         let (mut grid, _) = load_grid_from_ascii_with_config(
@@ -729,6 +2549,7 @@ NODATA_value -9999
             allow_diagonal: true,
             shelter_capacity: -1,
             path_algorithm: "dijkstra".to_string(),
+            ..GridConfig::default()
         };
         
         let (mut diagonal_grid, _) = load_grid_from_ascii_with_config(
@@ -744,6 +2565,7 @@ NODATA_value -9999
             allow_diagonal: false,
             shelter_capacity: -1,
             path_algorithm: "dijkstra".to_string(),
+            ..GridConfig::default()
         };
         
         let (mut cardinal_grid, _) = load_grid_from_ascii_with_config(
@@ -768,11 +2590,12 @@ NODATA_value -9999
     #[test]
     fn test_agent_statistics() -> io::Result<()> {
         // Create test agents directly
-        let mut agents = Vec::new();
-        agents.push(Agent::new(0, 1, 1, AgentType::Adult, true));
-        agents.push(Agent::new(1, 2, 2, AgentType::Child, true));
-        agents.push(Agent::new(2, 3, 3, AgentType::Teen, true));
-        agents.push(Agent::new(3, 4, 4, AgentType::Elder, true));
+        let agents = vec![
+            Agent::new(0, 1, 1, AgentType::Adult, true),
+            Agent::new(1, 2, 2, AgentType::Child, true),
+            Agent::new(2, 3, 3, AgentType::Teen, true),
+            Agent::new(3, 4, 4, AgentType::Elder, true),
+        ];
         
         // Setup test output directory
         let output_dir = Path::new("test_output");
@@ -795,4 +2618,62 @@ NODATA_value -9999
         clean_test_data();
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Test shelters spanning several cells that are entered through designated entrances
+    #[test]
+    fn test_shelter_entrances() -> io::Result<()> {
+        let test_dir = Path::new("test_data");
+        fs::create_dir_all(test_dir)?;
+        let grid_path = test_dir.join("entrance_grid.asc");
+        let grid_content = "ncols 5
+nrows 2
+xllcorner 100.0
+yllcorner 200.0
+cellsize 5.0
+NODATA_value -9999
+1 1 1 2001 2001
+1 1 1 1 e2001";
+        fs::write(&grid_path, grid_content)?;
+
+        let (mut grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+        grid.compute_distance_to_shelters();
+        assert_eq!(*grid.shelter_entrances, [(4, 1, 1)]);
+        assert_eq!(grid.shelter_entrance_cells(1), vec![(4, 1)]);
+        assert!(grid.is_shelter_entrance(4, 1));
+        assert!(grid.is_shelter_interior(3, 0));
+
+        // Entrances are written as a list and indexed again when read back
+        let json = serde_json::to_value(&grid)?;
+        assert_eq!(json["shelter_entrances"], serde_json::json!([[4, 1, 1]]));
+        let restored: grid::Grid = serde_json::from_value(json)?;
+        assert_eq!(restored.shelter_entrances, grid.shelter_entrances);
+        assert!(restored.is_shelter_entrance(4, 1) && restored.is_shelter_interior(3, 0));
+
+        // Only the entrance is a source; the rest of the footprint is not routed through
+        assert_eq!(grid.distance_to_shelter[1][4], Some(0));
+        assert_ne!(grid.distance_to_shelter[0][3], Some(0));
+        assert_ne!(grid.distance_to_shelter[0][4], Some(0));
+
+        // An agent next to the footprint walks round to the entrance
+        let agent = Agent::new(0, 2, 0, AgentType::Adult, true);
+        grid.add_agent(2, 0, 0);
+        let mut model = simulation_game::Model::new(grid, vec![agent]);
+        model.set_seed(3);
+        for step in 0..40 {
+            model.step(step, false, 0);
+        }
+        assert_eq!(model.shelter_arrivals.len(), 1);
+        assert_eq!(model.shelter_arrivals[0].shelter_id, 1);
+        assert_eq!((model.agents[0].x, model.agents[0].y), (4, 1));
+        assert_eq!(model.grid.shelter_occupancy(1), 1);
+
+        // Without entrances every cell of a shelter can be entered
+        fs::write(&grid_path, grid_content.replace("e2001", "2001"))?;
+        let (grid, _) = load_grid_from_ascii(&grid_path.to_string_lossy())?;
+        assert_eq!(grid.shelter_entrance_cells(1).len(), 3);
+        assert!(grid.is_shelter_entrance(3, 0));
+
+        clean_test_data();
+        Ok(())
+    }
+}
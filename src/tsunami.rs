@@ -0,0 +1,862 @@
+//! Loading of tsunami inundation frames from directories of ESRI ASCII grids
+
+use crate::grid::Grid;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Inundation depth in metres for one frame, indexed [y][x]
+pub type TsunamiFrame = Vec<Vec<f32>>;
+
+/// How depth is sampled between two tsunami frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum FrameInterpolation {
+    /// Use the current frame as is until the next one takes over
+    #[default]
+    Discrete,
+    /// Linear interpolation between the frames either side of the sample time
+    Linear,
+    /// Linear interpolation, but never below the deepest earlier frame, so a
+    /// cell stays at its peak depth once the front has passed
+    MaxSoFar,
+}
+
+/// Configuration for reading a series of tsunami frames
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TsunamiSeriesConfig {
+    /// Regular expression matched against file names; the first capture group
+    /// is the frame index used for ordering (default: `(\d+)\.asc$`)
+    pub file_pattern: String,
+    /// Keep every n-th frame after sorting (1 = keep all)
+    pub frame_stride: usize,
+    /// Seconds between consecutive frames on disk, before striding
+    pub frame_time_step: f64,
+    /// Reject files whose header does not match the grid
+    pub validate_header: bool,
+    /// Stream frames from disk, keeping at most this many decoded frames in
+    /// memory (default: load every frame up front)
+    #[serde(default)]
+    pub stream_cache_frames: Option<usize>,
+    /// Directory for the binary frame cache written on first read when
+    /// streaming (default: `.frame_cache` inside the frame directory)
+    #[serde(default)]
+    pub binary_cache_dir: Option<String>,
+}
+
+impl Default for TsunamiSeriesConfig {
+    fn default() -> Self {
+        TsunamiSeriesConfig {
+            file_pattern: r"(\d+)\.asc$".to_string(),
+            frame_stride: 1,
+            frame_time_step: 28.0,
+            validate_header: true,
+            stream_cache_frames: None,
+            binary_cache_dir: None,
+        }
+    }
+}
+
+impl TsunamiSeriesConfig {
+    /// Builder-style method to set the file name pattern
+    pub fn with_file_pattern(mut self, pattern: &str) -> Self {
+        self.file_pattern = pattern.to_string();
+        self
+    }
+
+    /// Builder-style method to set the frame stride
+    pub fn with_frame_stride(mut self, stride: usize) -> Self {
+        self.frame_stride = stride;
+        self
+    }
+
+    /// Builder-style method to set the time between frames on disk
+    pub fn with_frame_time_step(mut self, seconds: f64) -> Self {
+        self.frame_time_step = seconds;
+        self
+    }
+
+    /// Seconds between consecutive retained frames (stride × frame time step)
+    pub fn frame_interval(&self) -> f64 {
+        self.frame_stride.max(1) as f64 * self.frame_time_step
+    }
+
+    /// Builder-style method to stream frames from disk, keeping at most
+    /// `cache_frames` decoded frames in memory
+    pub fn with_streaming(mut self, cache_frames: usize) -> Self {
+        self.stream_cache_frames = Some(cache_frames);
+        self
+    }
+}
+
+/// Header of an ESRI ASCII grid file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AsciiGridHeader {
+    pub ncols: u32,
+    pub nrows: u32,
+    pub xllcorner: f64,
+    pub yllcorner: f64,
+    pub cellsize: f64,
+    pub nodata_value: Option<f64>,
+}
+
+impl AsciiGridHeader {
+    /// Parse the six header lines of an ASCII grid
+    pub fn read<I>(lines: &mut I) -> io::Result<Self>
+    where
+        I: Iterator<Item = io::Result<String>>,
+    {
+        let mut ncols = None;
+        let mut nrows = None;
+        let mut xllcorner = None;
+        let mut yllcorner = None;
+        let mut cellsize = None;
+        let mut nodata_value = None;
+
+        for _ in 0..6 {
+            let line = lines
+                .next()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Truncated grid header"))??;
+            let mut tokens = line.split_whitespace();
+            let key = tokens.next().unwrap_or("").to_ascii_lowercase();
+            let value: f64 = tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid grid header line: {}", line),
+                    )
+                })?;
+
+            match key.as_str() {
+                "ncols" => ncols = Some(value as u32),
+                "nrows" => nrows = Some(value as u32),
+                "xllcorner" | "xllcenter" => xllcorner = Some(value),
+                "yllcorner" | "yllcenter" => yllcorner = Some(value),
+                "cellsize" => cellsize = Some(value),
+                "nodata_value" => nodata_value = Some(value),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown grid header key: {}", key),
+                    ))
+                }
+            }
+        }
+
+        let missing = |name: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Missing {} in grid header", name))
+        };
+
+        Ok(AsciiGridHeader {
+            ncols: ncols.ok_or_else(|| missing("ncols"))?,
+            nrows: nrows.ok_or_else(|| missing("nrows"))?,
+            xllcorner: xllcorner.ok_or_else(|| missing("xllcorner"))?,
+            yllcorner: yllcorner.ok_or_else(|| missing("yllcorner"))?,
+            cellsize: cellsize.ok_or_else(|| missing("cellsize"))?,
+            nodata_value,
+        })
+    }
+
+    /// Header describing the grid's raster
+    pub fn for_grid(grid: &Grid, nodata_value: Option<f64>) -> Self {
+        AsciiGridHeader {
+            ncols: grid.ncol,
+            nrows: grid.nrow,
+            xllcorner: grid.xllcorner,
+            yllcorner: grid.yllcorner,
+            cellsize: grid.cellsize,
+            nodata_value,
+        }
+    }
+
+    /// Write the header lines of an ASCII grid
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "ncols        {}", self.ncols)?;
+        writeln!(writer, "nrows        {}", self.nrows)?;
+        writeln!(writer, "xllcorner    {}", self.xllcorner)?;
+        writeln!(writer, "yllcorner    {}", self.yllcorner)?;
+        writeln!(writer, "cellsize     {}", self.cellsize)?;
+        if let Some(nodata_value) = self.nodata_value {
+            writeln!(writer, "NODATA_value {}", nodata_value)?;
+        }
+        Ok(())
+    }
+
+    /// Check that this header describes the same raster as the grid
+    pub fn validate_against(&self, grid: &Grid) -> Result<(), String> {
+        if self.ncols != grid.ncol || self.nrows != grid.nrow {
+            return Err(format!(
+                "dimensions {}x{} do not match grid {}x{}",
+                self.ncols, self.nrows, grid.ncol, grid.nrow
+            ));
+        }
+
+        // Allow for rounding in how corner coordinates were written
+        let tolerance = grid.cellsize * 1e-3;
+        if (self.cellsize - grid.cellsize).abs() > tolerance {
+            return Err(format!(
+                "cellsize {} does not match grid cellsize {}",
+                self.cellsize, grid.cellsize
+            ));
+        }
+        if (self.xllcorner - grid.xllcorner).abs() > tolerance
+            || (self.yllcorner - grid.yllcorner).abs() > tolerance
+        {
+            return Err(format!(
+                "lower-left corner ({}, {}) does not match grid ({}, {})",
+                self.xllcorner, self.yllcorner, grid.xllcorner, grid.yllcorner
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Ordered series of tsunami depth frames matching a grid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TsunamiSeries {
+    /// Inundation depth in metres per frame, indexed [frame][y][x]
+    pub frames: Vec<Vec<Vec<f32>>>,
+    /// Frame index parsed from each retained file name
+    pub frame_indices: Vec<u64>,
+    /// Seconds between consecutive retained frames (stride × frame time step)
+    pub frame_interval: f64,
+}
+
+impl TsunamiSeries {
+    /// Load all frames in `dir` matching the configured pattern, ordered by
+    /// frame index and subsampled by the frame stride
+    pub fn load(dir: &str, grid: &Grid, config: &TsunamiSeriesConfig) -> io::Result<Self> {
+        let files = Self::list_frames(dir, config)?;
+
+        let frames: Vec<Vec<Vec<f32>>> = files
+            .par_iter()
+            .map(|(_, path)| read_validated_frame(path, grid, config.validate_header))
+            .collect::<io::Result<_>>()?;
+
+        Ok(TsunamiSeries {
+            frames,
+            frame_indices: files.iter().map(|&(index, _)| index).collect(),
+            frame_interval: config.frame_interval(),
+        })
+    }
+
+    /// Frame files in `dir` in load order, after applying the stride
+    pub fn list_frames(dir: &str, config: &TsunamiSeriesConfig) -> io::Result<Vec<(u64, PathBuf)>> {
+        let pattern = Regex::new(&config.file_pattern).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid tsunami file pattern {}: {}", config.file_pattern, e),
+            )
+        })?;
+
+        let mut files: Vec<(u64, PathBuf)> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                let index = pattern.captures(name)?.get(1)?.as_str().parse::<u64>().ok()?;
+                Some((index, path))
+            })
+            .collect();
+
+        files.sort();
+
+        let stride = config.frame_stride.max(1);
+        let files: Vec<(u64, PathBuf)> = files.into_iter().step_by(stride).collect();
+
+        if files.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No tsunami files matching {} in {}", config.file_pattern, dir),
+            ));
+        }
+
+        Ok(files)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// Access to a decoded frame, either borrowed from in-memory storage or
+/// shared with a streaming source's cache
+pub enum FrameRef<'a> {
+    Borrowed(&'a TsunamiFrame),
+    Shared(Arc<TsunamiFrame>),
+}
+
+impl Deref for FrameRef<'_> {
+    type Target = TsunamiFrame;
+
+    fn deref(&self) -> &TsunamiFrame {
+        match self {
+            FrameRef::Borrowed(frame) => frame,
+            FrameRef::Shared(frame) => frame,
+        }
+    }
+}
+
+impl FrameRef<'_> {
+    /// Depth at (x, y), 0 outside the frame
+    pub fn depth(&self, x: u32, y: u32) -> f32 {
+        self.get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+/// Provider of tsunami depth frames for a grid
+pub trait TsunamiSource: Send + Sync + fmt::Debug {
+    /// Number of frames in the series
+    fn frame_count(&self) -> usize;
+
+    /// Decoded frame at `index`
+    fn frame(&self, index: usize) -> io::Result<FrameRef<'_>>;
+}
+
+fn frame_out_of_range(index: usize, count: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Tsunami frame {} out of range ({} frames)", index, count),
+    )
+}
+
+/// In-memory source: every frame decoded and held at once
+impl TsunamiSource for Vec<TsunamiFrame> {
+    fn frame_count(&self) -> usize {
+        self.len()
+    }
+
+    fn frame(&self, index: usize) -> io::Result<FrameRef<'_>> {
+        self.get(index)
+            .map(FrameRef::Borrowed)
+            .ok_or_else(|| frame_out_of_range(index, self.len()))
+    }
+}
+
+impl TsunamiSource for TsunamiSeries {
+    fn frame_count(&self) -> usize {
+        self.frames.frame_count()
+    }
+
+    fn frame(&self, index: usize) -> io::Result<FrameRef<'_>> {
+        self.frames.frame(index)
+    }
+}
+
+/// Magic bytes of the binary frame cache: ncols and nrows as little-endian
+/// u32 follow, then the depths row by row as little-endian f32
+const FRAME_CACHE_MAGIC: &[u8; 4] = b"TSF1";
+
+/// Source that reads frames from disk on demand, keeping a small LRU of
+/// decoded frames. The first read of a frame also writes it to a binary cache
+/// so later reads, in this run or the next, skip parsing the ASCII grid.
+#[derive(Debug)]
+pub struct StreamingTsunami {
+    files: Vec<(u64, PathBuf)>,
+    ncols: usize,
+    nrows: usize,
+    cache_dir: PathBuf,
+    capacity: usize,
+    recent: Mutex<VecDeque<(usize, Arc<TsunamiFrame>)>>,
+}
+
+impl StreamingTsunami {
+    /// Index the frames in `dir` and check their headers, without decoding them
+    pub fn open(dir: &str, grid: &Grid, config: &TsunamiSeriesConfig) -> io::Result<Self> {
+        let files = TsunamiSeries::list_frames(dir, config)?;
+
+        if config.validate_header {
+            files.par_iter().try_for_each(|(_, path)| {
+                let file = fs::File::open(path)?;
+                let header = AsciiGridHeader::read(&mut BufReader::new(file).lines())?;
+                header.validate_against(grid).map_err(|message| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Tsunami file {:?}: {}", path, message),
+                    )
+                })
+            })?;
+        }
+
+        let cache_dir = match &config.binary_cache_dir {
+            Some(cache_dir) => PathBuf::from(cache_dir),
+            None => Path::new(dir).join(".frame_cache"),
+        };
+
+        Ok(StreamingTsunami {
+            files,
+            ncols: grid.ncol as usize,
+            nrows: grid.nrow as usize,
+            cache_dir,
+            capacity: config.stream_cache_frames.unwrap_or(1).max(1),
+            recent: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Frame index parsed from each file name, in load order
+    pub fn frame_indices(&self) -> Vec<u64> {
+        self.files.iter().map(|&(index, _)| index).collect()
+    }
+
+    /// Path of the binary cache file for a frame
+    pub fn cache_path(&self, index: usize) -> PathBuf {
+        let (frame_index, path) = &self.files[index];
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .map(|stem| stem.to_string())
+            .unwrap_or_else(|| frame_index.to_string());
+        self.cache_dir.join(format!("{}.tsf", stem))
+    }
+
+    fn decode(&self, index: usize) -> io::Result<TsunamiFrame> {
+        let source = &self.files[index].1;
+        let cache = self.cache_path(index);
+
+        // Use the cache unless the ASCII file has changed since it was written
+        let fresh = match (fs::metadata(&cache), fs::metadata(source)) {
+            (Ok(cached), Ok(original)) => match (cached.modified(), original.modified()) {
+                (Ok(cached), Ok(original)) => cached >= original,
+                _ => false,
+            },
+            _ => false,
+        };
+        if fresh {
+            if let Ok(frame) = self.read_cache(&cache) {
+                return Ok(frame);
+            }
+        }
+
+        let frame = read_tsunami_frame(source, self.ncols, self.nrows)?;
+        if let Err(e) = self.write_cache(&cache, &frame) {
+            eprintln!("Could not write tsunami frame cache {:?}: {}", cache, e);
+        }
+        Ok(frame)
+    }
+
+    fn read_cache(&self, path: &Path) -> io::Result<TsunamiFrame> {
+        let mut bytes = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut bytes)?;
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt frame cache {:?}", path));
+        let word = |offset: usize| -> io::Result<[u8; 4]> {
+            bytes
+                .get(offset..offset + 4)
+                .and_then(|slice| slice.try_into().ok())
+                .ok_or_else(invalid)
+        };
+
+        if &word(0)? != FRAME_CACHE_MAGIC
+            || u32::from_le_bytes(word(4)?) as usize != self.ncols
+            || u32::from_le_bytes(word(8)?) as usize != self.nrows
+            || bytes.len() != 12 + 4 * self.ncols * self.nrows
+        {
+            return Err(invalid());
+        }
+
+        Ok(bytes[12..]
+            .chunks_exact(4 * self.ncols)
+            .map(|row| {
+                row.chunks_exact(4)
+                    .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn write_cache(&self, path: &Path, frame: &TsunamiFrame) -> io::Result<()> {
+        fs::create_dir_all(&self.cache_dir)?;
+
+        let mut bytes = Vec::with_capacity(12 + 4 * self.ncols * self.nrows);
+        bytes.extend_from_slice(FRAME_CACHE_MAGIC);
+        bytes.extend_from_slice(&(self.ncols as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.nrows as u32).to_le_bytes());
+        for row in frame {
+            for value in row {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        // Write then rename so a concurrent reader never sees a partial file
+        let partial = path.with_extension("tsf.partial");
+        fs::File::create(&partial)?.write_all(&bytes)?;
+        fs::rename(&partial, path)
+    }
+}
+
+impl TsunamiSource for StreamingTsunami {
+    fn frame_count(&self) -> usize {
+        self.files.len()
+    }
+
+    fn frame(&self, index: usize) -> io::Result<FrameRef<'_>> {
+        if index >= self.files.len() {
+            return Err(frame_out_of_range(index, self.files.len()));
+        }
+
+        {
+            let mut recent = self.recent.lock().unwrap();
+            if let Some(position) = recent.iter().position(|&(cached, _)| cached == index) {
+                let entry = recent.remove(position).unwrap();
+                let frame = Arc::clone(&entry.1);
+                recent.push_back(entry);
+                return Ok(FrameRef::Shared(frame));
+            }
+        }
+
+        // Decode outside the lock so other threads can use cached frames
+        let frame = Arc::new(self.decode(index)?);
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back((index, Arc::clone(&frame)));
+        while recent.len() > self.capacity {
+            recent.pop_front();
+        }
+        Ok(FrameRef::Shared(frame))
+    }
+}
+
+/// Shared handle to a tsunami source stored on the grid. Handles compare equal
+/// when they point at the same source.
+#[derive(Clone, Debug, Default)]
+pub struct TsunamiSourceHandle(pub Option<Arc<dyn TsunamiSource>>);
+
+impl PartialEq for TsunamiSourceHandle {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Load the frames in `dir` into the grid: decoded up front, or streamed from
+/// disk when `stream_cache_frames` is set. Returns the number of frames.
+pub fn load_into_grid(dir: &str, grid: &mut Grid, config: &TsunamiSeriesConfig) -> io::Result<usize> {
+    if config.stream_cache_frames.is_some() {
+        let source = StreamingTsunami::open(dir, grid, config)?;
+        let frame_count = source.frame_count();
+        grid.set_tsunami_source(Arc::new(source));
+        Ok(frame_count)
+    } else {
+        let series = TsunamiSeries::load(dir, grid, config)?;
+        let frame_count = series.len();
+        grid.tsunami_data = series.frames;
+        grid.tsunami_source = TsunamiSourceHandle::default();
+        Ok(frame_count)
+    }
+}
+
+/// Running maximum depth over the frames seen so far, used by
+/// `FrameInterpolation::MaxSoFar` so earlier frames are read only once
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaxDepthEnvelope {
+    frames_folded: usize,
+    depth: TsunamiFrame,
+}
+
+impl MaxDepthEnvelope {
+    /// Fold frames up to and including `through` into the envelope. Moving
+    /// back in time rebuilds it from the first frame.
+    pub fn advance(&mut self, source: &dyn TsunamiSource, through: usize) -> io::Result<()> {
+        if through + 1 < self.frames_folded {
+            *self = MaxDepthEnvelope::default();
+        }
+
+        let through = through.min(source.frame_count().saturating_sub(1));
+        while self.frames_folded <= through && self.frames_folded < source.frame_count() {
+            let frame = source.frame(self.frames_folded)?;
+            if self.depth.is_empty() {
+                self.depth = frame.clone();
+            } else {
+                for (envelope_row, row) in self.depth.iter_mut().zip(frame.iter()) {
+                    for (envelope, &depth) in envelope_row.iter_mut().zip(row) {
+                        *envelope = envelope.max(depth);
+                    }
+                }
+            }
+            self.frames_folded += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Deepest water at (x, y) over the folded frames
+    pub fn depth(&self, x: u32, y: u32) -> f32 {
+        self.depth
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+/// NODATA value written for cells that never flood
+pub const HAZARD_NODATA: f64 = -9999.0;
+
+/// Per-cell hazard derived from a whole frame series: when each cell first
+/// floods, how deep it gets and how long it stays wet. Times are in seconds
+/// from the first frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HazardMaps {
+    /// Time of the first frame deeper than `wet_depth`, None if never wet
+    pub arrival_time: Vec<Vec<Option<f64>>>,
+    /// Deepest water over all frames in metres
+    pub max_depth: TsunamiFrame,
+    /// Wet frames times the frame interval
+    pub wet_duration: Vec<Vec<f64>>,
+    /// Seconds between frames
+    pub frame_interval: f64,
+    /// Depth in metres a cell must exceed to count as wet
+    pub wet_depth: f32,
+}
+
+impl HazardMaps {
+    /// Fold every frame of a source into the hazard maps
+    pub fn compute(source: &dyn TsunamiSource, frame_interval: f64, wet_depth: f32) -> io::Result<Self> {
+        let mut maps = HazardMaps {
+            arrival_time: Vec::new(),
+            max_depth: Vec::new(),
+            wet_duration: Vec::new(),
+            frame_interval,
+            wet_depth,
+        };
+
+        for index in 0..source.frame_count() {
+            let frame = source.frame(index)?;
+            if maps.max_depth.is_empty() {
+                maps.arrival_time = frame.iter().map(|row| vec![None; row.len()]).collect();
+                maps.max_depth = frame.iter().map(|row| vec![0.0; row.len()]).collect();
+                maps.wet_duration = frame.iter().map(|row| vec![0.0; row.len()]).collect();
+            }
+
+            let time = index as f64 * frame_interval;
+            for (y, row) in frame.iter().enumerate() {
+                for (x, &depth) in row.iter().enumerate() {
+                    let max_depth = &mut maps.max_depth[y][x];
+                    *max_depth = max_depth.max(depth);
+                    if depth > wet_depth {
+                        maps.arrival_time[y][x].get_or_insert(time);
+                        maps.wet_duration[y][x] += frame_interval;
+                    }
+                }
+            }
+        }
+
+        Ok(maps)
+    }
+
+    /// Seconds from the first frame until (x, y) first floods, None if it never does
+    pub fn arrival_time(&self, x: u32, y: u32) -> Option<f64> {
+        self.arrival_time
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .flatten()
+    }
+
+    /// Deepest water at (x, y) over all frames
+    pub fn max_depth(&self, x: u32, y: u32) -> f32 {
+        self.max_depth
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Seconds (x, y) spends wet
+    pub fn wet_duration(&self, x: u32, y: u32) -> f64 {
+        self.wet_duration
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Seconds left before (x, y) floods, `elapsed` seconds after the first
+    /// frame: zero once it has flooded, None if it never does
+    pub fn time_until_flooded(&self, x: u32, y: u32, elapsed: f64) -> Option<f64> {
+        self.arrival_time(x, y).map(|arrival| (arrival - elapsed).max(0.0))
+    }
+
+    /// Write the maps to `arrival_time.asc`, `max_depth.asc` and
+    /// `wet_duration.asc` in `dir`, with the raster of `grid`. Cells that
+    /// never flood have NODATA arrival times.
+    pub fn write_ascii(&self, dir: &Path, grid: &Grid) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let header = AsciiGridHeader::for_grid(grid, Some(HAZARD_NODATA));
+
+        let write_map = |name: &str, value: &dyn Fn(u32, u32) -> f64| -> io::Result<()> {
+            let mut file = io::BufWriter::new(fs::File::create(dir.join(name))?);
+            header.write(&mut file)?;
+            for y in 0..grid.nrow {
+                let row: Vec<String> = (0..grid.ncol).map(|x| value(x, y).to_string()).collect();
+                writeln!(file, "{}", row.join(" "))?;
+            }
+            file.flush()
+        };
+
+        write_map("arrival_time.asc", &|x, y| self.arrival_time(x, y).unwrap_or(HAZARD_NODATA))?;
+        write_map("max_depth.asc", &|x, y| self.max_depth(x, y) as f64)?;
+        write_map("wet_duration.asc", &|x, y| self.wet_duration(x, y))?;
+        Ok(())
+    }
+}
+
+/// Depth lookup at one fractional frame position, holding the two frames
+/// either side of it so repeated lookups don't go back to the source
+pub struct DepthSampler<'a> {
+    lower: Option<FrameRef<'a>>,
+    upper: Option<FrameRef<'a>>,
+    lower_index: usize,
+    fraction: f32,
+    mode: FrameInterpolation,
+    envelope: Option<&'a MaxDepthEnvelope>,
+}
+
+impl<'a> DepthSampler<'a> {
+    /// Sampler at `position` (e.g. 2.25 is a quarter of the way from frame 2
+    /// to frame 3). Positions past the last frame hold its depth. For
+    /// `MaxSoFar`, `envelope` must have been advanced through the lower frame.
+    pub fn new(
+        source: &'a dyn TsunamiSource,
+        position: f64,
+        mode: FrameInterpolation,
+        envelope: Option<&'a MaxDepthEnvelope>,
+    ) -> Self {
+        let count = source.frame_count();
+        if count == 0 || position < 0.0 {
+            return DepthSampler {
+                lower: None,
+                upper: None,
+                lower_index: 0,
+                fraction: 0.0,
+                mode,
+                envelope: None,
+            };
+        }
+
+        let last = count - 1;
+        let position = position.min(last as f64);
+        let lower_index = position.floor() as usize;
+        let upper_index = (lower_index + 1).min(last);
+        let fraction = (position - lower_index as f64) as f32;
+
+        let load = |index: usize| match source.frame(index) {
+            Ok(frame) => Some(frame),
+            Err(e) => {
+                eprintln!("Error reading tsunami frame {}: {}", index, e);
+                None
+            }
+        };
+
+        let lower = load(lower_index);
+        let upper = match mode {
+            FrameInterpolation::Discrete => None,
+            _ if fraction == 0.0 => None,
+            _ => load(upper_index),
+        };
+
+        DepthSampler {
+            lower,
+            upper,
+            lower_index,
+            fraction,
+            mode,
+            envelope,
+        }
+    }
+
+    /// Index of the frame at or before the sample position
+    pub fn lower_index(&self) -> usize {
+        self.lower_index
+    }
+
+    /// Depth in metres at (x, y)
+    pub fn depth(&self, x: u32, y: u32) -> f32 {
+        let from = self.lower.as_ref().map_or(0.0, |frame| frame.depth(x, y));
+        if self.mode == FrameInterpolation::Discrete || self.fraction == 0.0 {
+            return self.envelope_depth(x, y).max(from);
+        }
+
+        let to = self.upper.as_ref().map_or(0.0, |frame| frame.depth(x, y));
+        let linear = from + (to - from) * self.fraction;
+        self.envelope_depth(x, y).max(linear)
+    }
+
+    fn envelope_depth(&self, x: u32, y: u32) -> f32 {
+        match (self.mode, self.envelope) {
+            (FrameInterpolation::MaxSoFar, Some(envelope)) => envelope.depth(x, y),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Read one tsunami frame, checking its header against the grid if asked
+fn read_validated_frame(path: &Path, grid: &Grid, validate_header: bool) -> io::Result<TsunamiFrame> {
+    if validate_header {
+        let file = fs::File::open(path)?;
+        let header = AsciiGridHeader::read(&mut BufReader::new(file).lines())?;
+        header.validate_against(grid).map_err(|message| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Tsunami file {:?}: {}", path, message),
+            )
+        })?;
+    }
+
+    read_tsunami_frame(path, grid.ncol as usize, grid.nrow as usize)
+}
+
+/// Read one tsunami frame as depth in metres. NODATA and negative values are
+/// treated as dry; missing rows and columns are filled with zeros.
+pub fn read_tsunami_frame(path: &Path, ncols: usize, nrows: usize) -> io::Result<TsunamiFrame> {
+    let file = fs::File::open(path)?;
+    let mut lines = BufReader::new(file).lines();
+    let header = AsciiGridHeader::read(&mut lines)?;
+
+    let mut frame = Vec::with_capacity(nrows);
+
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if frame.len() == nrows {
+            break;
+        }
+
+        let mut row: Vec<f32> = line
+            .split_whitespace()
+            .take(ncols)
+            .map(|token| match token.parse::<f64>() {
+                Ok(value) if Some(value) == header.nodata_value => 0.0,
+                Ok(value) => value.max(0.0) as f32,
+                Err(_) => 0.0,
+            })
+            .collect();
+        row.resize(ncols, 0.0);
+        frame.push(row);
+    }
+
+    // Fill missing rows with zeros if needed
+    frame.resize(nrows, vec![0.0; ncols]);
+
+    Ok(frame)
+}